@@ -1,12 +1,18 @@
-struct VertexOutput {
-    [[builtin(position)]] clip_position: vec4<f32>;
-    [[location(0)]] tex_coords: vec2<f32>;
-};
+#include "include/gltf_vertex.wgsl"
 
 [[stage(fragment)]]
 fn main(
     in: VertexOutput,
 ) -> [[location(0)]] vec4<f32> {
     // return textureLoad(texture_diffuse1, tex_coord);
+#ifdef WIREFRAME
+    // The edges drawn over the shaded mesh
+    return vec4<f32>(0.05, 0.05, 0.05, 0.0);
+#else
+#ifdef HAS_TEX_COORDS
     return vec4<f32>(in.tex_coords, 0.0, 0.0);
+#else
+    return vec4<f32>(normalize(in.normal) * 0.5 + 0.5, 0.0);
+#endif
+#endif
 }
\ No newline at end of file
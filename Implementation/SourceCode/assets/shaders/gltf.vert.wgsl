@@ -1,11 +1,5 @@
-struct CameraUniform {
-    view: mat4x4<f32>;
-    projection: mat4x4<f32>;
-    view_proj: mat4x4<f32>;
-};
-
-[[group(0), binding(0)]] 
-var<uniform> camera: CameraUniform;
+#include "include/camera.wgsl"
+#include "include/gltf_vertex.wgsl"
 
 struct VertexInput {
     [[location(0)]] normal: vec3<f32>;
@@ -13,17 +7,16 @@ struct VertexInput {
     [[location(2)]] tex_coords: vec2<f32>;
 };
 
-struct VertexOutput {
-    [[builtin(position)]] clip_position: vec4<f32>;
-    [[location(0)]] tex_coords: vec2<f32>;
-};
-
 [[stage(vertex)]]
 fn main(
     model: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
+#ifdef HAS_TEX_COORDS
     out.tex_coords = model.tex_coords;
+#else
+    out.normal = model.normal;
+#endif
     out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
     return out;
 }
\ No newline at end of file
@@ -0,0 +1,8 @@
+struct CameraUniform {
+    view: mat4x4<f32>;
+    projection: mat4x4<f32>;
+    view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]] 
+var<uniform> camera: CameraUniform;
@@ -0,0 +1,8 @@
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+#ifdef HAS_TEX_COORDS
+    [[location(0)]] tex_coords: vec2<f32>;
+#else
+    [[location(0)]] normal: vec3<f32>;
+#endif
+};
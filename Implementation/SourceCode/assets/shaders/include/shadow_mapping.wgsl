@@ -0,0 +1,12 @@
+struct Scene {
+  lightViewProjMatrix : mat4x4<f32>;
+  cameraViewProjMatrix : mat4x4<f32>;
+  lightPos : vec3<f32>;
+};
+
+struct Model {
+  modelMatrix : mat4x4<f32>;
+};
+
+[[group(0), binding(0)]] var<uniform> scene : Scene;
+[[group(1), binding(0)]] var<uniform> model : Model;
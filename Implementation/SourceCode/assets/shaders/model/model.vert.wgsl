@@ -1,15 +1,10 @@
-struct Camera {
-	proj_view: mat4x4<f32>;
-};
+#include "include/camera.wgsl"
 
 struct Model {
 	matrix: mat4x4<f32>;
 	inv_tr: mat4x4<f32>;
 };
 
-[[group(0), binding(0)]]
-var<uniform> camera: Camera;
-
 [[group(1), binding(0)]] 
 var<uniform> model: Model;
 
@@ -25,11 +20,11 @@ fn main(
     [[location(1)]] normal: vec3<f32>
 ) -> VertexOutput {
     // var v: VertexOutput;
-    // v.position = camera.proj_view * model.matrix * vec4<f32>(pos, 1.0);
+    // v.position = camera.view_proj * model.matrix * vec4<f32>(pos, 1.0);
     // v.normal = normalize((model.inv_tr * vec4<f32>(normal, 0.0)).xyz);
     // v.world_pos = (model.matrix * vec4<f32>(pos, 1.0)).xyz;
 
-    let v_position = camera.proj_view * model.matrix * vec4<f32>(pos, 1.0);
+    let v_position = camera.view_proj * model.matrix * vec4<f32>(pos, 1.0);
     let v_normal = normalize((model.inv_tr * vec4<f32>(normal, 0.0)).xyz);
     let v_world_pos = (model.matrix * vec4<f32>(pos, 1.0)).xyz;
 
@@ -1,27 +1,16 @@
-struct Scene {
-  lightViewProjMatrix : mat4x4<f32>;
-  cameraViewProjMatrix : mat4x4<f32>;
-  lightPos : vec3<f32>;
-};
-
-struct Model {
-  modelMatrix : mat4x4<f32>;
-};
-
-@group(0) @binding(0) var<uniform> scene : Scene;
-@group(1) @binding(0) var<uniform> model : Model;
+#include "include/shadow_mapping.wgsl"
 
 struct VertexOutput {
-  @location(0) shadowPos : vec3<f32>;
-  @location(1) fragPos : vec3<f32>;
-  @location(2) fragNorm : vec3<f32>;
+  [[location(0)]] shadowPos : vec3<f32>;
+  [[location(1)]] fragPos : vec3<f32>;
+  [[location(2)]] fragNorm : vec3<f32>;
 
-  @builtin(position) Position : vec4<f32>;
+  [[builtin(position)]] Position : vec4<f32>;
 };
 
-@stage(vertex)
-fn main(@location(0) position : vec3<f32>,
-        @location(1) normal : vec3<f32>) -> VertexOutput {
+[[stage(vertex)]]
+fn main([[location(0)]] position : vec3<f32>,
+        [[location(1)]] normal : vec3<f32>) -> VertexOutput {
   var output : VertexOutput;
 
   // XY is in (-1, 1) space, Z is in (0, 1) space
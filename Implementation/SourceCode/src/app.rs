@@ -1,27 +1,80 @@
 use super::ASSETS_PATH;
-use crate::assets::AssetServer;
+use crate::{
+    assets::AssetServer,
+    offscreen::{self, OffscreenTarget},
+    plugin::Plugin,
+    render::{RenderJob, RenderResult, RenderThread, RenderWindow, Renderer},
+    render_graph::{Node, NodeLabel, RenderGraph},
+    schedule::{Schedule, Stage, SystemDescriptor},
+    time::{FrameLimiter, Time},
+    window::{WindowDescriptor, WindowRequest, Windows},
+};
+
+use crate::ecs::event::EventUpdater;
+
+pub use crate::ecs::{Events, World};
 
 use std::{
-    borrow::{BorrowMut, Cow},
+    cell::Cell,
+    collections::HashMap,
     mem,
-    path::Path,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
 };
 
 use async_trait::async_trait;
 use bytemuck::{Pod, Zeroable};
+use thiserror::Error;
 use wgpu::util::DeviceExt;
 use winit::{
     dpi::PhysicalSize,
     event::Event,
-    event_loop::{ControlFlow, EventLoop},
+    event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
     platform::run_return::EventLoopExtRunReturn,
-    window::{Icon, Window, WindowBuilder},
+    window::{Fullscreen, Icon, Window, WindowBuilder, WindowId},
 };
 
-pub trait System: std::fmt::Debug {
-    fn init(&mut self, context: &Context);
-    fn process_events<'a>(&mut self, event: &'a Event<()>, context: &Context);
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("failed to create the window: {0}")]
+    Window(#[from] winit::error::OsError),
+    #[error("no graphics adapter found that can present to the window")]
+    NoAdapter,
+    #[error("failed to request the device: {0}")]
+    RequestDevice(#[from] wgpu::RequestDeviceError),
+    #[error("the surface is incompatible with the adapter")]
+    IncompatibleSurface,
+    #[error("failed to load the icon `{path}`: {reason}")]
+    Icon { path: PathBuf, reason: String },
+    #[error("{0}")]
+    InvalidRunner(&'static str),
+    #[error("failed to start the render thread: {0}")]
+    RenderThread(std::io::Error),
+    #[error("the renderer has no offscreen target to read the pixels from")]
+    NoOffscreenTarget,
+    #[error("failed to read the pixels back: {0}")]
+    ReadPixels(#[from] wgpu::BufferAsyncError),
+}
+
+pub trait System: std::fmt::Debug + Send {
+    /// Called once for every system before the `Startup` stage runs.
+    fn init(&mut self, _context: &Context, _world: &mut World) {}
+
+    /// Called for every winit event, in schedule order.
+    fn process_events<'a>(
+        &mut self,
+        _event: &'a Event<()>,
+        _context: &Context,
+        _world: &mut World,
+    ) {
+    }
+
+    /// Called whenever the stage the system was added to runs.
+    fn run(&mut self, _context: &Context, _world: &mut World) {}
 }
 
 #[derive(Debug)]
@@ -36,47 +89,461 @@ impl<T: 'static> Resource<T> {
     }
 }
 
-pub struct World {}
+#[derive(Debug)]
+enum Runner {
+    Window(Box<Resource<()>>),
+    Headless(PhysicalSize<u32>),
+}
 
 pub type RenderDevice = Arc<wgpu::Device>;
 pub type RenderQueue = Arc<wgpu::Queue>;
 pub type RenderInstance = wgpu::Instance;
+pub type RenderAdapter = Arc<wgpu::Adapter>;
 
 pub type SharedContext = Arc<Context>;
 
+/// Shared between the context of a window on the main thread and its copy on the render thread.
+#[derive(Debug, Clone)]
+pub enum RenderTarget {
+    Surface {
+        window: Arc<Window>,
+        surface: Arc<wgpu::Surface>,
+    },
+    Texture(Arc<OffscreenTarget>),
+}
+
+/// The texture the systems draw into for the current frame, acquired by the engine
+/// before the `Render` stage and presented after the `Present` stage.
+#[derive(Debug)]
+pub struct Frame {
+    pub view: wgpu::TextureView,
+    surface_texture: Option<wgpu::SurfaceTexture>,
+}
+
+impl Frame {
+    fn present(self) {
+        if let Some(surface_texture) = self.surface_texture {
+            surface_texture.present();
+        }
+    }
+}
+
+/// A copy of the next frame requested through the [`Context`].
+#[derive(Debug)]
+enum CaptureRequest {
+    Screenshot(PathBuf),
+    Frame(mpsc::Sender<image::RgbaImage>),
+}
+
+/// Outcome of acquiring the frame of a window, see [`Context::frame_status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameStatus {
+    /// No frame was rendered yet.
+    #[default]
+    Pending,
+    Acquired,
+    /// The surface was lost or outdated and had to be reconfigured before the frame was acquired.
+    Reconfigured,
+    /// Acquiring timed out, the window is minimized or the surface could not be recovered.
+    /// `Render` and `Present` were skipped for this frame.
+    Skipped,
+    /// The device ran out of memory, the app exits.
+    OutOfMemory,
+}
+
+impl FrameStatus {
+    /// Whether the `Render` and `Present` stages ran with a frame.
+    pub fn is_rendered(&self) -> bool {
+        matches!(self, FrameStatus::Acquired | FrameStatus::Reconfigured)
+    }
+}
+
+/// Render state of a single window, or of the offscreen target of a headless app.
+/// The contexts of all windows share one device and queue.
+///
+/// Every window has one context on the main thread, handed to the simulation and `Extract` systems,
+/// and a copy on the render thread that acquires and presents the frames.
+#[derive(Debug)]
 pub struct Context {
-    window: Arc<Window>,
+    target: RenderTarget,
     pub queue: RenderQueue,
     pub device: RenderDevice,
-    pub surface: wgpu::Surface,
-    pub adapter: wgpu::Adapter,
+    pub adapter: RenderAdapter,
     pub config: wgpu::SurfaceConfiguration,
+    /// MSAA sample count the render pipelines and their attachments are created with.
+    pub sample_count: u32,
+    frame: Option<Frame>,
+    frame_status: FrameStatus,
+    requested_present_mode: Cell<Option<wgpu::PresentMode>>,
+    /// Shared with the copy on the render thread, which fulfills them after the next `Present` stage.
+    captures: Arc<Mutex<Vec<CaptureRequest>>>,
 }
 
 impl Context {
-    pub fn window(&self) -> Arc<Window> {
-        self.window.clone()
+    pub fn window(&self) -> Option<Arc<Window>> {
+        match self.target {
+            RenderTarget::Surface { ref window, .. } => Some(window.clone()),
+            RenderTarget::Texture(_) => None,
+        }
+    }
+
+    pub fn window_id(&self) -> Option<WindowId> {
+        self.window().map(|window| window.id())
+    }
+
+    pub fn is_headless(&self) -> bool {
+        matches!(self.target, RenderTarget::Texture(_))
     }
 
+    /// The frame being rendered, only set on the render thread during the `Render` and `Present` stages.
+    pub fn frame(&self) -> Option<&Frame> {
+        self.frame.as_ref()
+    }
+
+    /// Outcome of acquiring the current frame, or the last one outside of `Render` and `Present`.
+    /// On the main thread this is the last frame the render thread finished.
+    pub fn frame_status(&self) -> FrameStatus {
+        self.frame_status
+    }
+
+    #[inline]
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+
+    /// Switches the present mode, the surface is reconfigured before the next frame is acquired.
+    /// wgpu 0.12 can't list the modes of a surface, it configures unsupported ones as `Fifo`
+    /// with a warning while [`Context::present_mode`] keeps returning the requested mode.
+    pub fn set_present_mode(&self, present_mode: wgpu::PresentMode) {
+        self.requested_present_mode.set(Some(present_mode));
+    }
+
+    /// Saves the next frame of this window as PNG once it was rendered,
+    /// the file is written in the background.
+    pub fn request_screenshot(&self, path: impl Into<PathBuf>) {
+        self.captures
+            .lock()
+            .unwrap()
+            .push(CaptureRequest::Screenshot(path.into()));
+    }
+
+    /// Copies the next rendered frame of this window back to the CPU, the returned receiver
+    /// gets the image once the render thread presented the frame.
+    pub fn capture_frame(&self) -> Receiver<image::RgbaImage> {
+        let (sender, receiver) = mpsc::channel();
+        self.captures
+            .lock()
+            .unwrap()
+            .push(CaptureRequest::Frame(sender));
+        receiver
+    }
+
+    /// Copies the frame into the requested captures, runs on the render thread before the frame is presented.
+    pub(crate) fn capture_frame_requests(&self) {
+        let requests = mem::take(&mut *self.captures.lock().unwrap());
+        if requests.is_empty() {
+            return;
+        }
+
+        let texture = match self.frame_texture() {
+            Some(texture) => texture,
+            None => {
+                log::warn!("No frame to capture");
+                return;
+            }
+        };
+
+        let size = PhysicalSize::new(self.config.width, self.config.height);
+        let mut image = match offscreen::read_texture(&self.device, &self.queue, texture, size) {
+            Ok(image) => image,
+            Err(error) => {
+                log::error!("Failed to capture the frame, {error}");
+                return;
+            }
+        };
+
+        // Surfaces are usually BGRA, PNGs are RGBA
+        if matches!(
+            self.config.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            image.pixels_mut().for_each(|pixel| pixel.0.swap(0, 2));
+        }
+
+        let mut paths = vec![];
+        for request in requests {
+            match request {
+                CaptureRequest::Screenshot(path) => paths.push(path),
+                // The requester may have given up on the frame
+                CaptureRequest::Frame(sender) => {
+                    let _ = sender.send(image.clone());
+                }
+            }
+        }
+
+        if paths.is_empty() {
+            return;
+        }
+
+        thread::spawn(move || {
+            for path in paths {
+                let saved = match path.parent() {
+                    Some(parent) => {
+                        std::fs::create_dir_all(parent).map_err(image::ImageError::from)
+                    }
+                    None => Ok(()),
+                }
+                .and_then(|_| image.save(&path));
+
+                match saved {
+                    Ok(()) => log::info!("Saved the screenshot `{}`", path.display()),
+                    Err(error) => {
+                        log::error!(
+                            "Failed to save the screenshot `{}`, {error}",
+                            path.display()
+                        )
+                    }
+                }
+            }
+        });
+    }
+
+    /// The texture behind the current frame.
+    fn frame_texture(&self) -> Option<&wgpu::Texture> {
+        let frame = self.frame.as_ref()?;
+
+        match (frame.surface_texture.as_ref(), &self.target) {
+            (Some(surface_texture), _) => Some(&surface_texture.texture),
+            (None, RenderTarget::Texture(target)) => Some(&target.texture),
+            (None, RenderTarget::Surface { .. }) => None,
+        }
+    }
+
+    /// Copy of this context for the render thread, sharing the window, surface and device.
+    pub(crate) fn share(&self) -> Context {
+        Context {
+            target: self.target.clone(),
+            queue: self.queue.clone(),
+            device: self.device.clone(),
+            adapter: self.adapter.clone(),
+            config: self.config.clone(),
+            sample_count: self.sample_count,
+            frame: None,
+            frame_status: FrameStatus::default(),
+            requested_present_mode: Cell::new(None),
+            captures: self.captures.clone(),
+        }
+    }
+
+    /// Moves a present mode passed to [`Context::set_present_mode`] into the config.
+    pub(crate) fn apply_requested_present_mode(&mut self) {
+        if let Some(present_mode) = self.requested_present_mode.take() {
+            if present_mode != self.config.present_mode {
+                log::info!("Switching the present mode to {present_mode:?}");
+                self.config.present_mode = present_mode;
+            }
+        }
+    }
+
+    /// Takes over the config of the main thread's context, reconfiguring the target if it changed.
+    pub(crate) fn sync_config(&mut self, config: &wgpu::SurfaceConfiguration) {
+        if *config != self.config {
+            self.config = config.clone();
+            self.configure();
+        }
+    }
+
+    pub(crate) fn set_frame_status(&mut self, status: FrameStatus) {
+        self.frame_status = status;
+    }
+
+    /// Acquires the frame for the `Render` stage, recovering from a lost or outdated surface.
+    pub(crate) fn acquire_frame(&mut self) -> FrameStatus {
+        self.frame_status = match self.target {
+            RenderTarget::Texture(ref target) => {
+                self.frame = Some(Frame {
+                    view: target
+                        .texture
+                        .create_view(&wgpu::TextureViewDescriptor::default()),
+                    surface_texture: None,
+                });
+                FrameStatus::Acquired
+            }
+            // Minimized, the surface can't be configured with a zero size
+            RenderTarget::Surface { .. } if self.config.width == 0 || self.config.height == 0 => {
+                FrameStatus::Skipped
+            }
+            RenderTarget::Surface { ref surface, .. } => {
+                let (texture, status) = match surface.get_current_texture() {
+                    Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                        log::debug!("Reconfiguring the lost or outdated surface");
+                        surface.configure(&self.device, &self.config);
+                        (surface.get_current_texture(), FrameStatus::Reconfigured)
+                    }
+                    texture => (texture, FrameStatus::Acquired),
+                };
+
+                match texture {
+                    Ok(texture) => {
+                        self.frame = Some(Frame {
+                            view: texture
+                                .texture
+                                .create_view(&wgpu::TextureViewDescriptor::default()),
+                            surface_texture: Some(texture),
+                        });
+                        status
+                    }
+                    Err(wgpu::SurfaceError::OutOfMemory) => {
+                        log::error!("Out of memory while acquiring the frame");
+                        FrameStatus::OutOfMemory
+                    }
+                    Err(error) => {
+                        log::warn!("Skipping the frame, {error}");
+                        FrameStatus::Skipped
+                    }
+                }
+            }
+        };
+
+        self.frame_status
+    }
+
+    pub(crate) fn present_frame(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            frame.present();
+        }
+    }
+
+    /// Copies the offscreen target back to the CPU, fails when rendering to a window.
+    pub fn read_pixels(&self) -> Result<image::RgbaImage, AppError> {
+        match self.target {
+            RenderTarget::Texture(ref target) => Ok(target.read_pixels(&self.device, &self.queue)?),
+            RenderTarget::Surface { .. } => Err(AppError::NoOffscreenTarget),
+        }
+    }
+
+    /// Opens a window with its own surface that renders with the device and queue of this context.
+    pub fn create_window(
+        &self,
+        target: &EventLoopWindowTarget<()>,
+        instance: &wgpu::Instance,
+        descriptor: &WindowDescriptor,
+    ) -> Result<Context, AppError> {
+        let window = WindowBuilder::new()
+            .with_title(&descriptor.title)
+            .with_inner_size(descriptor.size)
+            .build(target)?;
+
+        let surface = unsafe { instance.create_surface(&window) };
+        let format = surface
+            .get_preferred_format(&self.adapter)
+            .ok_or(AppError::IncompatibleSurface)?;
+
+        let size = window.inner_size();
+        let config = wgpu::SurfaceConfiguration {
+            format,
+            width: size.width,
+            height: size.height,
+            ..self.config.clone()
+        };
+        surface.configure(&self.device, &config);
+
+        Ok(Context {
+            target: RenderTarget::Surface {
+                window: Arc::new(window),
+                surface: Arc::new(surface),
+            },
+            queue: self.queue.clone(),
+            device: self.device.clone(),
+            adapter: self.adapter.clone(),
+            config,
+            sample_count: self.sample_count,
+            frame: None,
+            frame_status: FrameStatus::default(),
+            requested_present_mode: Cell::new(None),
+            captures: Arc::default(),
+        })
+    }
+
+    /// Updates the size in the config, the render thread reconfigures the target before the next frame.
     pub fn resize(&mut self, size: &PhysicalSize<u32>) {
         self.config.width = size.width;
         self.config.height = size.height;
-        self.surface.configure(&self.device, &self.config);
+    }
+
+    /// Applies the config to the surface or recreates the offscreen target.
+    fn configure(&mut self) {
+        // Minimized, the surface can't be configured with a zero size
+        if self.config.width == 0 || self.config.height == 0 {
+            return;
+        }
+
+        match self.target {
+            RenderTarget::Surface { ref surface, .. } => {
+                surface.configure(&self.device, &self.config)
+            }
+            RenderTarget::Texture(ref mut target) => {
+                let size = PhysicalSize::new(self.config.width, self.config.height);
+                *target = Arc::new(OffscreenTarget::new(&self.device, size))
+            }
+        }
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct AppOptions {
-    pub title: Option<&'static str>,
-    pub assets_root: Option<&'static str>,
-    pub icon: Option<&'static str>,
+    pub title: Option<String>,
+    pub assets_root: Option<PathBuf>,
+    /// Defaults to `icon.png` in the assets folder.
+    pub icon: Option<PathBuf>,
+    /// Defaults to the size of the primary monitor.
+    pub size: Option<PhysicalSize<u32>>,
+    pub fullscreen: bool,
+    pub present_mode: wgpu::PresentMode,
+    pub backends: wgpu::Backends,
+    /// MSAA sample count of the color and depth targets.
+    pub sample_count: u32,
+    /// Initial cap of the [`FrameLimiter`] resource.
+    pub max_fps: Option<f64>,
+    /// Requests a software adapter, used for reproducible offscreen renders.
+    pub force_fallback_adapter: bool,
+}
+
+impl AppOptions {
+    /// The assets folder, relative roots are resolved against the crate root like the `AssetServer` does.
+    pub fn assets_path(&self) -> PathBuf {
+        match self.assets_root {
+            Some(ref root) => Path::new(env!("CARGO_MANIFEST_DIR")).join(root),
+            None => PathBuf::from(ASSETS_PATH),
+        }
+    }
+}
+
+impl Default for AppOptions {
+    fn default() -> Self {
+        Self {
+            title: None,
+            assets_root: None,
+            icon: None,
+            size: None,
+            fullscreen: false,
+            present_mode: wgpu::PresentMode::Fifo,
+            backends: wgpu::Backends::all(),
+            sample_count: 1,
+            max_fps: None,
+            force_fallback_adapter: false,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct App {
-    resources: Resource<()>,
-    systems: Vec<Box<dyn System>>,
-    asset_server: AssetServer,
+    runner: Runner,
+    world: World,
+    schedule: Schedule,
+    /// Nodes of the primary window, added to the schedule as one system once the app runs.
+    render_graph: RenderGraph,
+    options: AppOptions,
 }
 
 impl App {
@@ -84,146 +551,514 @@ impl App {
         App::default()
     }
 
-    pub fn init(options: AppOptions) -> Self {
+    pub fn init(options: AppOptions) -> Result<Self, AppError> {
         // winit::event_loop::EventLoop::with_user_event();
         let event_loop = EventLoop::new();
-        let primary_monitor = event_loop.primary_monitor().unwrap();
 
-        let asset_server = match options.assets_root {
-            Some(root) => AssetServer::new(root),
-            None => AssetServer::default(),
+        let icon_path = match options.icon {
+            Some(ref icon) => icon.clone(),
+            None => options.assets_path().join("icon.png"),
         };
+        let icon = load_icon(&icon_path)
+            .map_err(|error| log::warn!("Starting without icon, {error}"))
+            .ok();
 
-        let icon = load_icon(&Path::new(ASSETS_PATH).join("icon.png"));
+        let mut builder = WindowBuilder::new().with_window_icon(icon);
 
-        let mut builder = WindowBuilder::new()
-            .with_inner_size(primary_monitor.size())
-            .with_window_icon(Some(icon));
+        builder = match (options.size, event_loop.primary_monitor()) {
+            (Some(size), _) => builder.with_inner_size(size),
+            (None, Some(primary_monitor)) => builder.with_inner_size(primary_monitor.size()),
+            (None, None) => builder,
+        };
 
         builder = match options.title {
-            Some(title) => builder.with_title(Cow::Borrowed(title)),
+            Some(ref title) => builder.with_title(title),
             None => builder,
         };
 
-        let window = builder.build(&event_loop).unwrap();
+        if options.fullscreen {
+            builder = builder.with_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
+
+        let window = builder.build(&event_loop)?;
+
+        Ok(Self {
+            runner: Runner::Window(Box::new(Resource::new(window, event_loop))),
+            world: default_world(&options),
+            schedule: Schedule::default(),
+            render_graph: RenderGraph::default(),
+            options,
+        })
+    }
 
+    /// Creates an app without a window, the systems render into an offscreen texture
+    /// of the given size which is returned by [`App::render_headless`].
+    pub fn headless(options: AppOptions, size: PhysicalSize<u32>) -> Self {
         Self {
-            resources: Resource::new(window, event_loop),
-            asset_server,
-            systems: vec![],
+            runner: Runner::Headless(size),
+            world: default_world(&options),
+            schedule: Schedule::default(),
+            render_graph: RenderGraph::default(),
+            options,
+        }
+    }
+
+    /// Adds a system, by default to the `Update` stage.
+    /// Pass a [`SystemDescriptor`] to pick the stage, labels, ordering and run conditions.
+    pub fn add_system(&mut self, system: impl Into<SystemDescriptor>) -> &mut Self {
+        self.schedule.add_system(system);
+        self
+    }
+
+    pub fn add_system_to_stage(
+        &mut self,
+        stage: Stage,
+        system: impl System + 'static,
+    ) -> &mut Self {
+        self.add_system(SystemDescriptor::new(system).in_stage(stage))
+    }
+
+    /// Adds a pass to the [`RenderGraph`] of the primary window.
+    pub fn add_render_node(&mut self, label: NodeLabel, node: impl Node + 'static) -> &mut Self {
+        self.render_graph.add_node(label, node);
+        self
+    }
+
+    /// Registers `Events<T>` as resource and swaps its buffers at the beginning of every frame.
+    pub fn add_event<T: Send + 'static>(&mut self) -> &mut Self {
+        if self.world.contains_resource::<Events<T>>() {
+            return self;
+        }
+
+        self.world.insert_resource(Events::<T>::default());
+        self.add_system_to_stage(Stage::PreUpdate, EventUpdater::<T>::default())
+    }
+
+    /// Opens the window once the app runs, see [`Windows::create`] to open windows from systems.
+    pub fn add_window(&mut self, request: WindowRequest) -> &mut Self {
+        if let Some(windows) = self.world.get_resource_mut::<Windows>() {
+            windows.create(request);
         }
+        self
     }
 
-    pub fn add_system(mut self, system: Box<dyn System>) -> Self {
-        self.systems.push(system);
+    pub fn add_plugin(&mut self, plugin: impl Plugin) -> &mut Self {
+        log::debug!("Adding plugin {}", plugin.name());
+        plugin.build(self);
         self
     }
 
+    /// Adds a resource every system can reach through [`World::get_resource`],
+    /// replacing any previous resource of the same type.
+    pub fn insert_resource<T: Send + 'static>(&mut self, resource: T) -> &mut Self {
+        self.world.insert_resource(resource);
+        self
+    }
+
+    pub fn get_resource<T: 'static>(&self) -> Option<&T> {
+        self.world.get_resource::<T>()
+    }
+
+    pub fn get_resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.world.get_resource_mut::<T>()
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Panics if the window can't be created, use [`App::init`] to handle the error.
     fn empty() -> App {
-        let event_loop = EventLoop::new();
-        let window = WindowBuilder::new()
-            .with_inner_size(event_loop.primary_monitor().unwrap().size())
-            .build(&event_loop)
-            .unwrap();
+        App::init(AppOptions::default()).expect("Failed to create the app")
+    }
 
-        Self {
-            resources: Resource::new(window, event_loop),
-            asset_server: AssetServer::default(),
-            systems: vec![],
+    /// Moves the render nodes into the schedule, the graph runs in the `Render` stage.
+    fn add_render_graph(&mut self) {
+        if self.render_graph.is_empty() {
+            return;
         }
+
+        let render_graph = mem::take(&mut self.render_graph);
+        self.add_system(
+            SystemDescriptor::new(render_graph)
+                .in_stage(Stage::Render)
+                .label(RenderGraph::LABEL),
+        );
     }
 
-    pub fn run(self) {
-        pollster::block_on(self.run_async());
+    /// Only returns if the startup fails, afterwards the event loop takes over the thread.
+    pub fn run(self) -> Result<(), AppError> {
+        pollster::block_on(self.run_async())
     }
 
-    async fn run_async(mut self) {
-        log::info!("Initializing the surface...");
+    /// Initializes the systems, renders a single frame offscreen and returns its pixels.
+    pub fn render_headless(self) -> Result<image::RgbaImage, AppError> {
+        pollster::block_on(self.render_headless_async())
+    }
 
-        let App { resources, .. } = self;
+    async fn render_headless_async(mut self) -> Result<image::RgbaImage, AppError> {
+        self.add_render_graph();
+
+        let size = match self.runner {
+            Runner::Headless(size) => size,
+            Runner::Window(_) => {
+                return Err(AppError::InvalidRunner(
+                    "render_headless requires an app created with App::headless",
+                ))
+            }
+        };
 
-        let Resource { window, .. } = resources;
+        log::info!("Initializing the offscreen target...");
 
         let WgpuResources {
-            surface,
             adapter,
             device,
             queue,
-            instance,
             config,
-        } = init_wgpu_resources(&window).await;
+            ..
+        } = init_wgpu_resources(None, size, &self.options).await?;
+
+        let target = RenderTarget::Texture(Arc::new(OffscreenTarget::new(&device, size)));
+
+        let context = Context {
+            target,
+            adapter: Arc::new(adapter),
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+            config,
+            sample_count: self.options.sample_count,
+            frame: None,
+            frame_status: FrameStatus::default(),
+            requested_present_mode: Cell::new(None),
+            captures: Arc::default(),
+        };
+
+        let App {
+            schedule, world, ..
+        } = &mut self;
+
+        // Without a window to keep responsive the renderer runs inline
+        let mut render_world = World::new();
+        let mut renderer = Renderer::default();
+        let render_window = RenderWindow {
+            context: context.share(),
+            schedule: schedule.split_render_stages(),
+        };
+
+        schedule.startup(&context, world);
+        schedule.run_frame(&context, world);
+        schedule.run_extract(&context, world, &mut render_world);
+
+        renderer.render(RenderJob {
+            world: render_world,
+            configs: vec![(None, context.config.clone())],
+            added: vec![render_window],
+            removed: vec![],
+            target_fps: None,
+        });
+
+        renderer
+            .context(None)
+            .ok_or(AppError::NoOffscreenTarget)?
+            .read_pixels()
+    }
+
+    async fn run_async(mut self) -> Result<(), AppError> {
+        log::info!("Initializing the surface...");
+        self.add_render_graph();
+
+        let App {
+            runner,
+            mut world,
+            mut schedule,
+            options,
+            ..
+        } = self;
+
+        let resources = match runner {
+            Runner::Window(resources) => resources,
+            Runner::Headless(_) => {
+                return Err(AppError::InvalidRunner(
+                    "Headless apps are run with App::render_headless",
+                ))
+            }
+        };
 
-        let device = Arc::new(device);
-        let queue = Arc::new(queue);
-        let window = Arc::new(window);
+        let Resource { window, .. } = *resources;
 
-        let context = Arc::new(Mutex::new(Context {
-            window,
+        let WgpuResources {
+            instance,
             surface,
             adapter,
             device,
             queue,
             config,
-        }));
+        } = init_wgpu_resources(Some(&window), window.inner_size(), &options).await?;
 
-        context
-            .lock()
-            .and_then(|val| {
-                self.systems
-                    .iter_mut()
-                    .for_each(|system| system.init(&*val));
+        let primary_id = window.id();
+        let mut context = Context {
+            target: RenderTarget::Surface {
+                window: Arc::new(window),
+                surface: Arc::new(surface.unwrap()),
+            },
+            adapter: Arc::new(adapter),
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+            config,
+            sample_count: options.sample_count,
+            frame: None,
+            frame_status: FrameStatus::default(),
+            requested_present_mode: Cell::new(None),
+            captures: Arc::default(),
+        };
+
+        if let Some(windows) = world.get_resource_mut::<Windows>() {
+            windows.set_primary(primary_id);
+        }
 
-                Ok(())
-            })
-            .unwrap();
+        let primary_render = RenderWindow {
+            context: context.share(),
+            schedule: schedule.split_render_stages(),
+        };
+        schedule.startup(&context, &mut world);
 
-        resources.event_loop.run(move |event, _, control_flow| {
-            use winit::event::Event::*;
-            use winit::event::WindowEvent::*;
+        let mut render_thread =
+            RenderThread::spawn(Renderer::default(), resources.event_loop.create_proxy())
+                .map_err(AppError::RenderThread)?;
+        // The frame worlds that aren't in flight, extracted into and handed to the render thread
+        let mut frame_worlds = (0..RenderThread::FRAMES_IN_FLIGHT)
+            .map(|_| World::new())
+            .collect::<Vec<_>>();
+        let mut added_windows = vec![primary_render];
+        let mut removed_windows = vec![];
 
-            *control_flow = ControlFlow::Wait;
+        let mut secondary_windows = HashMap::<WindowId, SecondaryWindow>::new();
 
-            {
-                context
-                    .lock()
-                    .and_then(|val| {
-                        self.systems
-                            .iter_mut()
-                            .for_each(|system| system.process_events(&event, &*val));
-
-                        Ok(())
-                    })
-                    .unwrap();
-            }
+        resources
+            .event_loop
+            .run(move |event, target, control_flow| {
+                use winit::event::Event::*;
+                use winit::event::WindowEvent::*;
 
-            match event {
-                WindowEvent {
-                    ref event,
-                    window_id,
-                } if window_id == context.lock().unwrap().window().id() => match event {
-                    CloseRequested => *control_flow = ControlFlow::Exit,
-                    Resized(size) => {
-                        context
-                            .lock()
-                            .as_mut()
-                            .and_then(|val| Ok(val.resize(size)))
-                            .unwrap();
+                *control_flow = ControlFlow::Wait;
+
+                let world = &mut world;
+
+                // Window events and redraws only reach the systems of their window
+                let event_window = match event {
+                    WindowEvent { window_id, .. } | RedrawRequested(window_id) => Some(window_id),
+                    _ => None,
+                };
+
+                match event_window {
+                    Some(window_id) if window_id == primary_id => {
+                        schedule.process_events(&event, &context, world)
                     }
-                    ScaleFactorChanged { new_inner_size, .. } => {
-                        context
-                            .lock()
-                            .as_mut()
-                            .and_then(|val| Ok(val.resize(new_inner_size)))
-                            .unwrap();
+                    Some(window_id) => {
+                        if let Some(window) = secondary_windows.get_mut(&window_id) {
+                            window.process_events(&event, world);
+                        }
                     }
-                    _ => {}
-                },
-                _ => (),
-            }
-        });
+                    None => {
+                        schedule.process_events(&event, &context, world);
+                        secondary_windows
+                            .values_mut()
+                            .for_each(|window| window.process_events(&event, world));
+                    }
+                }
+
+                // Frames drawn since the last event, their worlds are free again
+                loop {
+                    let RenderResult {
+                        world: frame_world,
+                        statuses,
+                    } = match render_thread.try_receive() {
+                        Ok(result) => result,
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => {
+                            log::error!("The render thread stopped");
+                            *control_flow = ControlFlow::Exit;
+                            return;
+                        }
+                    };
+                    frame_worlds.push(frame_world);
+
+                    for (window_id, status) in statuses {
+                        match window_id {
+                            Some(id) if id == primary_id => context.set_frame_status(status),
+                            Some(id) => {
+                                if let Some(window) = secondary_windows.get_mut(&id) {
+                                    window.context.set_frame_status(status);
+                                }
+                            }
+                            None => {}
+                        }
+
+                        if status == FrameStatus::OutOfMemory {
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    }
+                }
+
+                match event {
+                    WindowEvent {
+                        ref event,
+                        window_id,
+                    } if window_id == primary_id => match event {
+                        CloseRequested => *control_flow = ControlFlow::Exit,
+                        Resized(size) => context.resize(size),
+                        ScaleFactorChanged { new_inner_size, .. } => context.resize(new_inner_size),
+                        _ => {}
+                    },
+                    WindowEvent {
+                        ref event,
+                        window_id,
+                    } => match (event, secondary_windows.get_mut(&window_id)) {
+                        (CloseRequested, Some(_)) => {
+                            secondary_windows.remove(&window_id);
+                            removed_windows.push(window_id);
+                            if let Some(windows) = world.get_resource_mut::<Windows>() {
+                                windows.remove(window_id);
+                            }
+                        }
+                        (Resized(size), Some(window)) => window.context.resize(size),
+                        (ScaleFactorChanged { new_inner_size, .. }, Some(window)) => {
+                            window.context.resize(new_inner_size)
+                        }
+                        _ => {}
+                    },
+                    // The primary window drives the frames of every window
+                    RedrawRequested(window_id) if window_id == primary_id => {
+                        // Every frame world is in flight, the render thread wakes the loop once one is drawn
+                        let mut frame_world = match frame_worlds.pop() {
+                            Some(frame_world) => frame_world,
+                            None => return,
+                        };
+
+                        schedule.run_frame(&context, world);
+                        secondary_windows.values_mut().for_each(|window| {
+                            window.schedule.run_update_stages(&window.context, world)
+                        });
+
+                        context.apply_requested_present_mode();
+                        schedule.run_extract(&context, world, &mut frame_world);
+                        secondary_windows.values_mut().for_each(|window| {
+                            window.context.apply_requested_present_mode();
+                            window
+                                .schedule
+                                .run_extract(&window.context, world, &mut frame_world);
+                        });
+
+                        let configs = std::iter::once(&context)
+                            .chain(secondary_windows.values().map(|window| &window.context))
+                            .map(|context| (context.window_id(), context.config.clone()))
+                            .collect();
+
+                        let submitted = render_thread.submit(RenderJob {
+                            world: frame_world,
+                            configs,
+                            added: mem::take(&mut added_windows),
+                            removed: mem::take(&mut removed_windows),
+                            target_fps: world
+                                .get_resource::<FrameLimiter>()
+                                .and_then(FrameLimiter::target_fps),
+                        });
+                        if !submitted {
+                            log::error!("The render thread stopped");
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    }
+                    // A frame was drawn and its world is free
+                    UserEvent(()) => {
+                        if let Some(window) = context.window() {
+                            window.request_redraw();
+                        }
+                    }
+                    MainEventsCleared => {
+                        open_requested_windows(target, &instance, &context, world)
+                            .into_iter()
+                            .for_each(|(window, render_window)| {
+                                let id = window.context.window_id().unwrap();
+                                secondary_windows.insert(id, window);
+                                added_windows.push(render_window);
+                            });
+
+                        // Without a free frame world the redraw waits for the render thread instead
+                        if let Some(window) = context.window().filter(|_| !frame_worlds.is_empty())
+                        {
+                            window.request_redraw();
+                        }
+                    }
+                    LoopDestroyed => render_thread.shutdown(),
+                    _ => (),
+                }
+            });
+    }
+}
+
+/// The main thread side of a window opened at runtime, simulated by its own systems.
+#[derive(Debug)]
+struct SecondaryWindow {
+    context: Context,
+    schedule: Schedule,
+}
+
+impl SecondaryWindow {
+    fn process_events(&mut self, event: &Event<()>, world: &mut World) {
+        self.schedule.process_events(event, &self.context, world);
     }
 }
 
+/// Opens the windows requested through the [`Windows`] resource and starts their systems,
+/// their render systems start on the render thread once it receives the window.
+fn open_requested_windows(
+    target: &EventLoopWindowTarget<()>,
+    instance: &wgpu::Instance,
+    primary: &Context,
+    world: &mut World,
+) -> Vec<(SecondaryWindow, RenderWindow)> {
+    let requests = match world.get_resource_mut::<Windows>() {
+        Some(windows) => windows.take_requests(),
+        None => return vec![],
+    };
+
+    requests
+        .into_iter()
+        .filter_map(|request| {
+            let context = primary
+                .create_window(target, instance, &request.descriptor)
+                .map_err(|error| {
+                    log::error!("Failed to open `{}`, {error}", request.descriptor.title)
+                })
+                .ok()?;
+
+            let mut schedule = Schedule::default();
+            request
+                .systems
+                .into_iter()
+                .for_each(|system| schedule.add_system(system));
+
+            let render_window = RenderWindow {
+                context: context.share(),
+                schedule: schedule.split_render_stages(),
+            };
+            schedule.startup(&context, world);
+
+            if let (Some(windows), Some(id)) =
+                (world.get_resource_mut::<Windows>(), context.window_id())
+            {
+                windows.add(id);
+            }
+
+            Some((SecondaryWindow { context, schedule }, render_window))
+        })
+        .collect()
+}
+
 impl Default for App {
     fn default() -> Self {
         App::empty()
@@ -232,83 +1067,120 @@ impl Default for App {
 
 struct WgpuResources {
     instance: RenderInstance,
-    surface: wgpu::Surface,
+    surface: Option<wgpu::Surface>,
     adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
 }
 
-fn check_gpu_features(adapter: &wgpu::Adapter) {
-    let required_features = wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::DEPTH_CLIP_CONTROL;
-    let supported_features = adapter.features();
+/// Features the renderer uses when the adapter has them and works around otherwise,
+/// check `context.device.features()` before relying on one of them.
+pub const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::POLYGON_MODE_LINE
+    .union(wgpu::Features::DEPTH_CLIP_CONTROL)
+    .union(wgpu::Features::TIMESTAMP_QUERY)
+    .union(wgpu::Features::PIPELINE_STATISTICS_QUERY);
 
-    assert!(
-        supported_features.contains(required_features),
-        "Adapter Missing required: {:#?}",
-        (required_features - supported_features)
-    );
-    println!("{:#?}", adapter.get_info());
-}
+/// Picks the optional features the adapter supports.
+fn negotiate_features(adapter: &wgpu::Adapter) -> wgpu::Features {
+    let supported_features = adapter.features();
+    let missing_features = OPTIONAL_FEATURES - supported_features;
 
-async fn init_wgpu_resources(window: &Window) -> WgpuResources {
-    use wgpu::Features;
+    if !missing_features.is_empty() {
+        log::warn!("Adapter is missing optional features: {missing_features:?}");
+    }
+    log::info!("{:#?}", adapter.get_info());
 
-    let size = window.inner_size();
+    OPTIONAL_FEATURES & supported_features
+}
 
-    let instance = wgpu::Instance::new(wgpu::Backends::all());
-    let surface = unsafe { instance.create_surface(window) };
+/// Creates the device for the `window`, or a surfaceless one when `window` is `None`.
+async fn init_wgpu_resources(
+    window: Option<&Window>,
+    size: PhysicalSize<u32>,
+    options: &AppOptions,
+) -> Result<WgpuResources, AppError> {
+    let instance = wgpu::Instance::new(options.backends);
+    let surface = window.map(|window| unsafe { instance.create_surface(window) });
     let adapter = instance
         .request_adapter(&wgpu::RequestAdapterOptions {
             power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
+            compatible_surface: surface.as_ref(),
+            force_fallback_adapter: options.force_fallback_adapter,
         })
         .await
-        .unwrap();
+        .ok_or(AppError::NoAdapter)?;
 
     let (device, queue) = adapter
         .request_device(
             &wgpu::DeviceDescriptor {
-                features: Features::POLYGON_MODE_LINE | Features::DEPTH_CLIP_CONTROL,
+                features: negotiate_features(&adapter),
                 limits: wgpu::Limits::default(),
                 label: None,
             },
             None, // Trace path
         )
-        .await
-        .unwrap();
+        .await?;
+
+    let format = match surface {
+        Some(ref surface) => surface
+            .get_preferred_format(&adapter)
+            .ok_or(AppError::IncompatibleSurface)?,
+        None => OffscreenTarget::FORMAT,
+    };
 
     let config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-        format: surface.get_preferred_format(&adapter).unwrap(),
+        // Copied from by screenshots
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        format,
         width: size.width,
         height: size.height,
-        present_mode: wgpu::PresentMode::Fifo,
+        present_mode: options.present_mode,
     };
 
-    surface.configure(&device, &config);
-
-    check_gpu_features(&adapter);
+    if let Some(ref surface) = surface {
+        surface.configure(&device, &config);
+    }
 
-    return WgpuResources {
+    Ok(WgpuResources {
         surface,
         adapter,
         device,
         queue,
         config,
         instance,
+    })
+}
+
+/// World with the resources every app starts with.
+fn default_world(options: &AppOptions) -> World {
+    let asset_server = match options.assets_root {
+        Some(ref root) => AssetServer::new(root),
+        None => AssetServer::default(),
     };
+
+    let mut world = World::new();
+    world.insert_resource(asset_server);
+    world.insert_resource(Time::default());
+    world.insert_resource(FrameLimiter::new(options.max_fps));
+    world.insert_resource(Windows::default());
+    world
 }
 
-fn load_icon(path: &Path) -> Icon {
+fn load_icon(path: &Path) -> Result<Icon, AppError> {
+    let icon_error = |reason: String| AppError::Icon {
+        path: path.to_owned(),
+        reason,
+    };
+
     let (icon_rgba, icon_width, icon_height) = {
         let image = image::open(path)
-            .expect("Failed to open icon path")
+            .map_err(|error| icon_error(error.to_string()))?
             .into_rgba8();
         let (width, height) = image.dimensions();
         let rgba = image.into_raw();
         (rgba, width, height)
     };
-    Icon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open icon")
+    Icon::from_rgba(icon_rgba, icon_width, icon_height)
+        .map_err(|error| icon_error(error.to_string()))
 }
@@ -54,7 +54,7 @@ struct AssetLoader {
 }
 
 impl AssetLoader {
-    pub fn new(root: &'static str) -> Self {
+    pub fn new(root: impl AsRef<Path>) -> Self {
         Self {
             assets_path: Path::new(env!("CARGO_MANIFEST_DIR")).join(root),
         }
@@ -77,7 +77,7 @@ pub struct AssetServer {
 impl AssetServer {
     const PNG_MAGIC_NUMBER: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
 
-    pub fn new(root: &'static str) -> Self {
+    pub fn new(root: impl AsRef<Path>) -> Self {
         Self {
             server: AssetLoader::new(root),
         }
@@ -1,16 +1,13 @@
-use glam::{Mat3, Mat4, Vec2, Vec3, Vec3A};
+use glam::{Mat3, Mat4, Quat, Vec2, Vec3, Vec3A};
 use std::{
     f32::consts::PI,
     time::{SystemTime, UNIX_EPOCH},
 };
-use winit::{
-    dpi::{PhysicalPosition, PhysicalSize},
-    event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent},
-};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
 
 use std::{f32, f64, time::Duration};
 
-use crate::{app::Context, mesh::AABB};
+use crate::{mesh::AABB, pipeline::DepthMode};
 
 #[derive(Debug)]
 pub struct MyCamera {
@@ -20,8 +17,10 @@ pub struct MyCamera {
     pub aspect: f32,
     pub fovy: f32,
     pub z_near: f32,
+    /// Ignored with reversed-Z, which has no far plane.
     pub z_far: f32,
     pub center: glam::Vec3A,
+    pub depth_mode: DepthMode,
     time: SystemTime,
 }
 
@@ -33,6 +32,14 @@ impl MyCamera {
         [0.0, 0.0, 0.5, 1.0],
     ]);
 
+    /// Maps OpenGL's depth from -1 at the near plane to 1 at the far plane onto 1 to 0.
+    pub const OPENGL_TO_REVERSED_Z_MATRIX: Mat4 = Mat4::from_cols_array_2d(&[
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, -0.5, 0.0],
+        [0.0, 0.0, 0.5, 1.0],
+    ]);
+
     pub fn new(config: &wgpu::SurfaceConfiguration) -> Self {
         let time: SystemTime = SystemTime::now();
         MyCamera {
@@ -46,6 +53,7 @@ impl MyCamera {
             // z_far: f32::INFINITY,
             z_far: 1000.0,
             center: Default::default(),
+            depth_mode: DepthMode::Standard,
             time,
         }
     }
@@ -81,13 +89,22 @@ impl MyCamera {
         //     Mat4::from_axis_angle(axis, angle.to_radians()) * Mat4::from_cols_array(&[1.0; 16]);
 
         let view = glam::Mat4::look_at_rh(self.eye.into(), self.target.into(), self.up.into());
-        // let proj = glam::Mat4::perspective_infinite_rh_gl(self.fovy, self.aspect, self.z_near);
-        let mut proj = glam::Mat4::perspective_rh_gl(
-            self.fovy.to_radians(),
-            self.aspect,
-            self.z_near,
-            self.z_far,
-        );
+        let proj = match self.depth_mode {
+            DepthMode::Standard => glam::Mat4::perspective_rh_gl(
+                self.fovy.to_radians(),
+                self.aspect,
+                self.z_near,
+                self.z_far,
+            ),
+            DepthMode::ReversedZ => {
+                Self::OPENGL_TO_REVERSED_Z_MATRIX
+                    * <Mat4 as PerspectiveGL>::perspective_infinite_rh_gl(
+                        self.fovy.to_radians(),
+                        self.aspect,
+                        self.z_near,
+                    )
+            }
+        };
 
         // proj.col_mut(1).y *= -1.; // Vulkan’s projected Y is inverted from OpenGL’s
 
@@ -106,6 +123,7 @@ impl Default for MyCamera {
             z_near: Default::default(),
             z_far: Default::default(),
             center: Default::default(),
+            depth_mode: Default::default(),
             time: SystemTime::now(),
         }
     }
@@ -118,15 +136,20 @@ pub struct CameraController {
     speed: f32,
     theta: f32,
     phi: f32,
-    mouse_pressed: bool,
-    is_forward_pressed: bool,
-    is_backward_pressed: bool,
-    is_left_pressed: bool,
-    is_right_pressed: bool,
+    /// Steps of the speed the next update moves towards the target.
+    forward: f32,
+    /// Steps of the speed the next update orbits to the right.
+    orbit: f32,
     last_position: Option<PhysicalPosition<f64>>,
 }
 
 impl CameraController {
+    /// Orbit angle per second in radians, the old per frame step of `0.1` at 60 fps
+    /// turned the camera this fast at a distance of 1 from the target.
+    pub const ANIMATION_SPEED: f32 = 6.0;
+    /// Distance to the target kept per zoom step.
+    pub const ZOOM_FACTOR: f32 = 0.9;
+
     pub fn new(camera: MyCamera, speed: f32) -> Self {
         Self {
             speed,
@@ -135,95 +158,33 @@ impl CameraController {
         }
     }
 
-    pub fn process_events(&mut self, event: &WindowEvent, context: &Context) -> bool {
-        match event {
-            WindowEvent::MouseWheel { delta, .. } => match delta {
-                winit::event::MouseScrollDelta::LineDelta(x, y) => {
-                    // println!("LineDelta: {x} - {y}");
-                    false
-                }
-                winit::event::MouseScrollDelta::PixelDelta(PhysicalPosition { y, x }) => {
-                    self.radius = clamp(
-                        self.radius + *y as f32 * 0.001 * self.radius,
-                        self.radius / 16.0,
-                        f32::INFINITY,
-                    );
-
-                    // true
-                    false
-                }
-            },
-            WindowEvent::CursorMoved { position, .. } => {
-                // let last_position = self.last_position.clone();
-                // self.last_position = Some(position.clone());
-                // if self.mouse_pressed {
-                //     let movement_x = (position.x - last_position.unwrap_or(*position).x) as i32;
-                //     let movement_y = (position.y - last_position.unwrap_or(*position).y) as i32;
-
-                //     let PhysicalSize { width, height } = context.window().inner_size();
-                //     self.theta -= (movement_x / width as i32) as f32 * f32::consts::PI * 2.0;
-
-                //     self.phi = clamp(
-                //         self.phi - (movement_y / height as i32) as f32 * f32::consts::PI,
-                //         -f32::consts::PI / 2.0 + 0.1,
-                //         f32::consts::PI / 2.0 - 0.1,
-                //     );
-
-                //     return true;
-                // }
-
-                false
-            }
-            WindowEvent::MouseInput { state, button, .. } => {
-                self.mouse_pressed = match state {
-                    ElementState::Pressed => true,
-                    ElementState::Released => false,
-                };
-                false
-            }
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state,
-                        virtual_keycode: Some(keycode),
-                        ..
-                    },
-                ..
-            } => {
-                let is_pressed = *state == ElementState::Pressed;
-                match keycode {
-                    VirtualKeyCode::W | VirtualKeyCode::Up => {
-                        self.is_forward_pressed = is_pressed;
-                        true
-                    }
-                    VirtualKeyCode::A | VirtualKeyCode::Left => {
-                        self.is_left_pressed = is_pressed;
-                        true
-                    }
-                    VirtualKeyCode::S | VirtualKeyCode::Down => {
-                        self.is_backward_pressed = is_pressed;
-                        true
-                    }
-                    VirtualKeyCode::D | VirtualKeyCode::Right => {
-                        self.is_right_pressed = is_pressed;
-                        true
-                    }
-                    _ => false,
-                }
-            }
-            _ => false,
-        }
+    /// Moves the camera on the next update, in steps of its speed.
+    pub fn move_by(&mut self, forward: f32, orbit: f32) {
+        self.forward += forward;
+        self.orbit += orbit;
     }
 
-    pub fn animate(&mut self) {
+    /// Scales the distance to the target, positive `steps` move closer.
+    pub fn zoom(&mut self, steps: f32) {
         let forward = self.camera.target - self.camera.eye;
-        let forward_norm = forward.normalize();
+        let distance = clamp(
+            forward.length() * CameraController::ZOOM_FACTOR.powf(steps),
+            self.speed,
+            f32::INFINITY,
+        );
 
-        let right = forward_norm.cross(self.camera.up);
-        let forward = self.camera.target - self.camera.eye;
-        let forward_mag = forward.length();
+        self.camera.eye = self.camera.target - forward.normalize() * distance;
+    }
+
+    /// Orbits the camera around the target, `delta` is the frame time in seconds.
+    pub fn animate(&mut self, delta: f32) {
+        self.orbit_by(CameraController::ANIMATION_SPEED * delta);
+    }
 
-        self.camera.eye = self.camera.target - (forward - right * 0.1).normalize() * forward_mag;
+    /// Rotates the eye around the up axis through the target, positive angles orbit to the left.
+    fn orbit_by(&mut self, angle: f32) {
+        let rotation = Quat::from_axis_angle(self.camera.up.normalize().into(), angle);
+        self.camera.eye = self.camera.target + rotation * (self.camera.eye - self.camera.target);
     }
 
     pub fn update_camera(&mut self) -> Mat4 {
@@ -233,11 +194,9 @@ impl CameraController {
 
         // Prevents glitching when camera gets too close to the
         // center of the scene.
-        if self.is_forward_pressed && forward_mag > self.speed {
-            self.camera.eye += forward_norm * self.speed;
-        }
-        if self.is_backward_pressed {
-            self.camera.eye -= forward_norm * self.speed;
+        let step = self.forward * self.speed;
+        if step < 0.0 || forward_mag - step > self.speed {
+            self.camera.eye += forward_norm * step;
         }
 
         // if self.is_left_pressed {
@@ -253,19 +212,15 @@ impl CameraController {
         //     self.camera.eye += right;
         // }
 
-        let right = forward_norm.cross(self.camera.up);
         // Redo radius calc in case the fowrard/backward is pressed.
-        let forward = self.camera.target - self.camera.eye;
-        let forward_mag = forward.length();
+        let forward_mag = (self.camera.target - self.camera.eye).length();
 
-        if self.is_right_pressed {
-            self.camera.eye =
-                self.camera.target - (forward + right * self.speed).normalize() * forward_mag;
-        }
-        if self.is_left_pressed {
-            self.camera.eye =
-                self.camera.target - (forward - right * self.speed).normalize() * forward_mag;
+        // The orbit is an arc of `orbit` steps, its angle doesn't depend on how it's split up
+        if self.orbit != 0.0 {
+            self.orbit_by(-self.orbit * self.speed / forward_mag);
         }
+        self.forward = 0.0;
+        self.orbit = 0.0;
 
         let camera = &mut self.camera;
         // let translation = camera.eye - camera.center;
@@ -292,6 +247,7 @@ pub fn clamp<T: PartialOrd>(n: T, min: T, max: T) -> T {
     }
 }
 
+/// The infinite right-handed projection with OpenGL's depth of -1 to 1, which glam lacks.
 pub trait PerspectiveGL {
     #[inline]
     fn perspective_infinite_rh_gl(
@@ -347,3 +303,50 @@ impl WorldTrans {
         translate_matrix * scale_matrix
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller() -> CameraController {
+        let camera = MyCamera {
+            eye: glam::vec3a(0.0, 1.0, 4.0),
+            up: glam::Vec3A::Y,
+            ..Default::default()
+        };
+        CameraController::new(camera, 0.4)
+    }
+
+    #[test]
+    fn animating_is_independent_of_the_frame_rate() {
+        let mut once = controller();
+        once.animate(1.0 / 30.0);
+
+        let mut twice = controller();
+        twice.animate(1.0 / 60.0);
+        twice.animate(1.0 / 60.0);
+
+        assert!(once.camera.eye.abs_diff_eq(twice.camera.eye, 1e-5));
+        assert!((once.camera.eye.length() - controller().camera.eye.length()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn orbiting_is_independent_of_the_frame_rate() {
+        let mut once = controller();
+        once.move_by(0.0, 2.0);
+        once.update_camera();
+
+        let mut twice = controller();
+        for _ in 0..2 {
+            twice.move_by(0.0, 1.0);
+            twice.update_camera();
+        }
+
+        assert!(once.camera.eye.abs_diff_eq(twice.camera.eye, 1e-5));
+        let forward = once.camera.target - once.camera.eye;
+        assert!(
+            forward.x > 0.0,
+            "orbiting to the right turns the view to the right"
+        );
+    }
+}
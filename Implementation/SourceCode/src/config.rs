@@ -0,0 +1,493 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+use winit::dpi::PhysicalSize;
+
+use crate::{app::AppOptions, input::InputMap, loader::RenderMode, recorder::GifSettings};
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("failed to read the config `{path}`: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to parse the config `{path}`: {source}")]
+    Parse { path: PathBuf, source: ron::Error },
+    #[error("unknown argument `{0}`")]
+    UnknownArgument(String),
+    #[error("missing value for `{0}`")]
+    MissingValue(String),
+    #[error("invalid value `{value}` for `{name}`, expected {expected}")]
+    InvalidValue {
+        name: String,
+        value: String,
+        expected: &'static str,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresentMode {
+    Fifo,
+    Mailbox,
+    Immediate,
+}
+
+impl FromStr for PresentMode {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "fifo" | "vsync" => Ok(PresentMode::Fifo),
+            "mailbox" => Ok(PresentMode::Mailbox),
+            "immediate" => Ok(PresentMode::Immediate),
+            _ => Err("fifo, mailbox or immediate"),
+        }
+    }
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Lets wgpu pick the best backend of the platform.
+    Auto,
+    Vulkan,
+    Gl,
+    Metal,
+    Dx12,
+}
+
+impl FromStr for Backend {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "auto" => Ok(Backend::Auto),
+            "vulkan" => Ok(Backend::Vulkan),
+            "gl" | "opengl" => Ok(Backend::Gl),
+            "metal" => Ok(Backend::Metal),
+            "dx12" => Ok(Backend::Dx12),
+            _ => Err("auto, vulkan, gl, metal or dx12"),
+        }
+    }
+}
+
+impl From<Backend> for wgpu::Backends {
+    fn from(backend: Backend) -> Self {
+        match backend {
+            Backend::Auto => wgpu::Backends::all(),
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Gl => wgpu::Backends::GL,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Dx12 => wgpu::Backends::DX12,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// Defaults to the name of the model.
+    pub title: Option<String>,
+    /// Set together with `height`, defaults to the size of the primary monitor.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fullscreen: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RendererConfig {
+    pub present_mode: PresentMode,
+    pub backend: Backend,
+    /// 1 disables multisampling, wgpu guarantees support for 4.
+    pub msaa_samples: u32,
+    /// Software frame rate cap, `None` renders as fast as the present mode allows.
+    pub max_fps: Option<f64>,
+    /// Measures the passes with GPU queries and logs the result every second at the info level.
+    pub profile: bool,
+    /// How the model is drawn at startup, `M` switches modes at runtime.
+    pub render_mode: RenderMode,
+    /// Reversed-Z depth with an infinite far plane, more precise for large scenes.
+    pub reversed_z: bool,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            present_mode: PresentMode::Fifo,
+            backend: Backend::Auto,
+            msaa_samples: 1,
+            max_fps: None,
+            profile: false,
+            render_mode: RenderMode::Solid,
+            reversed_z: false,
+        }
+    }
+}
+
+/// Engine settings read from a RON file and overridden by command-line arguments.
+///
+/// ```ron
+/// (
+///     window: (width: Some(1280), height: Some(720), fullscreen: false),
+///     renderer: (present_mode: mailbox, backend: vulkan, msaa_samples: 4),
+///     model: "models/cube/Cube.glb",
+///     log_level: Info,
+/// )
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub window: WindowConfig,
+    pub renderer: RendererConfig,
+    /// Relative to the crate root, defaults to `assets`.
+    pub assets_root: Option<PathBuf>,
+    /// Relative to the asset root.
+    pub model: PathBuf,
+    pub log_level: log::LevelFilter,
+    /// Turntable recordings, started with `F10` or `--record-gif`.
+    pub gif: GifSettings,
+    /// Rebinds the actions and axes it lists, the others keep their default keys.
+    pub input: InputMap,
+    /// Records a turntable into this GIF once the model was loaded, command-line only.
+    #[serde(skip)]
+    pub record_gif: Option<PathBuf>,
+    /// Renders a single frame into this PNG instead of opening a window, command-line only.
+    #[serde(skip)]
+    pub headless: Option<PathBuf>,
+    /// Validates the shaders of the asset root instead of running, command-line only.
+    #[serde(skip)]
+    pub validate_shaders: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            window: WindowConfig::default(),
+            renderer: RendererConfig::default(),
+            assets_root: None,
+            model: PathBuf::from("models/dragon/Dragon.glb"),
+            log_level: log::LevelFilter::Warn,
+            gif: GifSettings::default(),
+            input: InputMap::default(),
+            record_gif: None,
+            headless: None,
+            validate_shaders: false,
+        }
+    }
+}
+
+impl Config {
+    /// Read when no `--config` argument is passed, if it exists.
+    pub const DEFAULT_PATH: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/config.ron");
+
+    pub const USAGE: &'static str = "\
+Options:
+    -h, --help                print this message
+    --config <file.ron>       config file, defaults to config.ron in the crate root
+    --width <px>              window width
+    --height <px>             window height
+    --fullscreen              borderless fullscreen
+    --windowed                disable fullscreen
+    --present-mode <mode>     fifo, mailbox or immediate
+    --backend <backend>       auto, vulkan, gl, metal or dx12
+    --msaa <samples>          1 or 4
+    --max-fps <fps>           frame rate cap, `off` to uncap
+    --profile                 log GPU timings of the passes, needs `--log-level info`
+    --render-mode <mode>      solid, wireframe, points or wireframe_on_solid
+    --reversed-z              reversed-Z depth with an infinite far plane
+    --assets <dir>            asset root
+    --model <file>            model to load, relative to the asset root
+    --log-level <level>       off, error, warn, info, debug or trace
+    --headless <file.png>     render a single frame offscreen
+    --record-gif <file.gif>   record a turntable once the model was loaded
+    --gif-frames <count>      frames of a turntable recording
+    --validate-shaders        check every shader of the asset root and exit";
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        ron::from_str(&source).map_err(|source| ConfigError::Parse {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    /// Loads the config file passed with `--config` or the default one and applies the other arguments on top.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Config, ConfigError> {
+        let args = args.into_iter().collect::<Vec<_>>();
+
+        let config_path = args
+            .iter()
+            .position(|arg| arg == "--config")
+            .map(|index| {
+                args.get(index + 1)
+                    .map(PathBuf::from)
+                    .ok_or_else(|| ConfigError::MissingValue("--config".to_owned()))
+            })
+            .transpose()?;
+
+        let mut config = match config_path {
+            Some(path) => Config::load(path)?,
+            None if Path::new(Config::DEFAULT_PATH).exists() => Config::load(Config::DEFAULT_PATH)?,
+            None => Config::default(),
+        };
+
+        config.apply_args(args)?;
+        Ok(config)
+    }
+
+    fn apply_args(&mut self, args: Vec<String>) -> Result<(), ConfigError> {
+        let mut args = args.into_iter();
+
+        while let Some(name) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| ConfigError::MissingValue(name.clone()))
+            };
+
+            match name.as_str() {
+                "--config" => {
+                    value()?;
+                }
+                "--width" => self.window.width = Some(parse(&name, value()?, "a size in pixels")?),
+                "--height" => {
+                    self.window.height = Some(parse(&name, value()?, "a size in pixels")?)
+                }
+                "--fullscreen" => self.window.fullscreen = true,
+                "--windowed" => self.window.fullscreen = false,
+                "--present-mode" => self.renderer.present_mode = parse_enum(&name, value()?)?,
+                "--backend" => self.renderer.backend = parse_enum(&name, value()?)?,
+                "--render-mode" => self.renderer.render_mode = parse_enum(&name, value()?)?,
+                "--max-fps" => {
+                    self.renderer.max_fps = match value()?.as_str() {
+                        "off" => None,
+                        fps => Some(parse(&name, fps.to_owned(), "frames per second or off")?),
+                    }
+                }
+                "--profile" => self.renderer.profile = true,
+                "--reversed-z" => self.renderer.reversed_z = true,
+                "--msaa" => self.renderer.msaa_samples = parse(&name, value()?, "1 or 4")?,
+                "--assets" => self.assets_root = Some(PathBuf::from(value()?)),
+                "--model" => self.model = PathBuf::from(value()?),
+                "--log-level" => {
+                    self.log_level =
+                        parse(&name, value()?, "off, error, warn, info, debug or trace")?
+                }
+                "--record-gif" => self.record_gif = Some(PathBuf::from(value()?)),
+                "--gif-frames" => self.gif.frames = parse(&name, value()?, "a frame count")?,
+                "--headless" => self.headless = Some(PathBuf::from(value()?)),
+                "--validate-shaders" => self.validate_shaders = true,
+                _ => return Err(ConfigError::UnknownArgument(name)),
+            }
+        }
+
+        self.validate()
+    }
+
+    /// Checks the values the arguments and the RON file can't rule out by their type.
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |name: &str, value: &dyn ToString, expected| {
+            Err(ConfigError::InvalidValue {
+                name: name.to_owned(),
+                value: value.to_string(),
+                expected,
+            })
+        };
+
+        match (self.window.width, self.window.height) {
+            (Some(_), None) => {
+                return invalid(
+                    "window.height",
+                    &"None",
+                    "a size in pixels next to the width",
+                )
+            }
+            (None, Some(_)) => {
+                return invalid(
+                    "window.width",
+                    &"None",
+                    "a size in pixels next to the height",
+                )
+            }
+            _ => {}
+        }
+        if !matches!(self.renderer.msaa_samples, 1 | 4) {
+            return invalid("msaa_samples", &self.renderer.msaa_samples, "1 or 4");
+        }
+        if self.gif.frames == 0 {
+            return invalid("gif.frames", &self.gif.frames, "at least one frame");
+        }
+        if !(1..=GifSettings::MAX_SPEED).contains(&self.gif.speed) {
+            return invalid("gif.speed", &self.gif.speed, "1 to 30");
+        }
+        if self.gif.width == Some(0) {
+            return invalid("gif.width", &0, "a size in pixels");
+        }
+        if self.gif.height == Some(0) {
+            return invalid("gif.height", &0, "a size in pixels");
+        }
+
+        Ok(())
+    }
+
+    /// Absolute asset root, see [`AppOptions::assets_path`].
+    pub fn assets_root(&self) -> PathBuf {
+        AppOptions {
+            assets_root: self.assets_root.clone(),
+            ..Default::default()
+        }
+        .assets_path()
+    }
+
+    pub fn model_path(&self) -> PathBuf {
+        self.assets_root().join(&self.model)
+    }
+
+    pub fn shaders_path(&self) -> PathBuf {
+        self.assets_root().join("shaders")
+    }
+
+    pub fn title(&self) -> String {
+        match self.window.title {
+            Some(ref title) => title.clone(),
+            None => self
+                .model
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| crate::WINDOW_TITLE.to_owned()),
+        }
+    }
+
+    /// Window size, `None` falls back to the monitor size or the headless default.
+    /// A width without a height, or the other way around, is rejected when the arguments are applied.
+    pub fn size(&self) -> Option<PhysicalSize<u32>> {
+        match (self.window.width, self.window.height) {
+            (Some(width), Some(height)) => Some(PhysicalSize::new(width, height)),
+            _ => None,
+        }
+    }
+
+    pub fn app_options(&self) -> AppOptions {
+        AppOptions {
+            title: Some(self.title()),
+            assets_root: self.assets_root.clone(),
+            size: self.size(),
+            fullscreen: self.window.fullscreen,
+            present_mode: self.renderer.present_mode.into(),
+            backends: self.renderer.backend.into(),
+            sample_count: self.renderer.msaa_samples,
+            max_fps: self.renderer.max_fps,
+            ..Default::default()
+        }
+    }
+}
+
+fn parse<T: FromStr>(name: &str, value: String, expected: &'static str) -> Result<T, ConfigError> {
+    value.parse().map_err(|_| ConfigError::InvalidValue {
+        name: name.to_owned(),
+        value,
+        expected,
+    })
+}
+
+fn parse_enum<T: FromStr<Err = &'static str>>(name: &str, value: String) -> Result<T, ConfigError> {
+    value.parse().map_err(|expected| ConfigError::InvalidValue {
+        name: name.to_owned(),
+        value,
+        expected,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ASSETS_PATH;
+
+    const DOCUMENTED: &str = r#"(
+        window: (width: Some(1280), height: Some(720), fullscreen: false),
+        renderer: (present_mode: mailbox, backend: vulkan, msaa_samples: 4),
+        model: "models/cube/Cube.glb",
+        log_level: Info,
+    )"#;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn the_documented_config_parses() {
+        let config: Config = ron::from_str(DOCUMENTED).unwrap();
+
+        assert_eq!(config.size(), Some(PhysicalSize::new(1280, 720)));
+        assert_eq!(config.renderer.present_mode, PresentMode::Mailbox);
+        assert_eq!(config.renderer.backend, Backend::Vulkan);
+        assert_eq!(config.renderer.msaa_samples, 4);
+        assert_eq!(config.model, Path::new("models/cube/Cube.glb"));
+        assert_eq!(config.log_level, log::LevelFilter::Info);
+        assert!(config.validate().is_ok());
+
+        // Missing fields keep their defaults
+        assert_eq!(config.renderer.max_fps, None);
+        assert_eq!(config.title(), "Cube");
+    }
+
+    #[test]
+    fn arguments_override_the_file() {
+        let mut config: Config = ron::from_str(DOCUMENTED).unwrap();
+        config
+            .apply_args(args(&["--width", "800", "--height", "600", "--msaa", "1"]))
+            .unwrap();
+
+        assert_eq!(config.size(), Some(PhysicalSize::new(800, 600)));
+        assert_eq!(config.renderer.msaa_samples, 1);
+        assert_eq!(config.renderer.present_mode, PresentMode::Mailbox);
+    }
+
+    #[test]
+    fn a_lone_width_or_height_is_rejected() {
+        let mut config = Config::default();
+        let error = config.apply_args(args(&["--width", "800"])).unwrap_err();
+        assert!(
+            matches!(error, ConfigError::InvalidValue { ref name, .. } if name == "window.height")
+        );
+
+        let config: Config = ron::from_str("(window: (height: Some(600)))").unwrap();
+        let error = config.validate().unwrap_err();
+        assert!(
+            matches!(error, ConfigError::InvalidValue { ref name, .. } if name == "window.width")
+        );
+    }
+
+    #[test]
+    fn relative_asset_roots_start_at_the_crate_root() {
+        let mut config = Config::default();
+        assert_eq!(config.assets_root(), Path::new(ASSETS_PATH));
+
+        config.apply_args(args(&["--assets", "other"])).unwrap();
+        assert_eq!(
+            config.shaders_path(),
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("other/shaders")
+        );
+    }
+}
@@ -0,0 +1,103 @@
+/// Handle to an entity in the [`World`](super::World).
+///
+/// The generation makes sure that a handle of a despawned entity
+/// doesn't point to a new entity that reused the same index.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    #[inline]
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Entities {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl Entities {
+    pub fn alloc(&mut self) -> Entity {
+        self.len += 1;
+
+        match self.free.pop() {
+            Some(index) => {
+                self.alive[index as usize] = true;
+                Entity {
+                    index,
+                    generation: self.generations[index as usize],
+                }
+            }
+            None => {
+                self.generations.push(0);
+                self.alive.push(true);
+                Entity {
+                    index: (self.generations.len() - 1) as u32,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    pub fn free(&mut self, entity: Entity) -> bool {
+        if !self.contains(entity) {
+            return false;
+        }
+
+        let index = entity.index();
+        self.alive[index] = false;
+        self.generations[index] += 1;
+        self.free.push(entity.index);
+        self.len -= 1;
+
+        true
+    }
+
+    #[inline]
+    pub fn contains(&self, entity: Entity) -> bool {
+        let index = entity.index();
+        index < self.alive.len()
+            && self.alive[index]
+            && self.generations[index] == entity.generation
+    }
+
+    /// Returns the entity living at `index`, if any.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<Entity> {
+        match self.alive.get(index) {
+            Some(true) => Some(Entity {
+                index: index as u32,
+                generation: self.generations[index],
+            }),
+            _ => None,
+        }
+    }
+
+    /// Number of slots that were ever allocated, alive or not.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.alive.len()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
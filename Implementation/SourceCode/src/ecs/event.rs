@@ -0,0 +1,211 @@
+use std::marker::PhantomData;
+
+use crate::app::{Context, System};
+
+use super::World;
+
+#[derive(Debug)]
+struct EventInstance<T> {
+    id: usize,
+    event: T,
+}
+
+/// Double buffered channel of events of type `T`, stored as a resource and registered with
+/// [`App::add_event`](crate::app::App::add_event).
+///
+/// Events are kept for two frames, so every system that runs once per frame sees each event
+/// exactly once through its own [`EventReader`], no matter if it runs before or after the writer.
+#[derive(Debug)]
+pub struct Events<T> {
+    previous: Vec<EventInstance<T>>,
+    current: Vec<EventInstance<T>>,
+    event_count: usize,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: vec![],
+            current: vec![],
+            event_count: 0,
+        }
+    }
+}
+
+impl<T> Events<T> {
+    pub fn send(&mut self, event: T) {
+        self.current.push(EventInstance {
+            id: self.event_count,
+            event,
+        });
+        self.event_count += 1;
+    }
+
+    /// Drops the events of the previous frame, called once per frame by [`EventUpdater`].
+    pub fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    /// Number of events sent since the channel was created.
+    #[inline]
+    pub fn event_count(&self) -> usize {
+        self.event_count
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn oldest_id(&self) -> usize {
+        self.previous
+            .first()
+            .or_else(|| self.current.first())
+            .map_or(self.event_count, |instance| instance.id)
+    }
+}
+
+/// Cursor into an [`Events`] channel, owned by the system that reads it.
+pub struct EventReader<T> {
+    last_event_count: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> std::fmt::Debug for EventReader<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventReader")
+            .field("event", &std::any::type_name::<T>())
+            .field("last_event_count", &self.last_event_count)
+            .finish()
+    }
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        Self {
+            last_event_count: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> EventReader<T> {
+    /// Events sent since the last call, oldest first.
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> + 'a {
+        let last_event_count = self.last_event_count;
+        if last_event_count < events.oldest_id() {
+            log::warn!(
+                "{} events of {} were dropped before they were read",
+                events.oldest_id() - last_event_count,
+                std::any::type_name::<T>()
+            );
+        }
+        self.last_event_count = events.event_count;
+
+        events
+            .previous
+            .iter()
+            .chain(events.current.iter())
+            .filter(move |instance| instance.id >= last_event_count)
+            .map(|instance| &instance.event)
+    }
+
+    /// Like [`EventReader::read`] but reads the channel out of the world's resources.
+    pub fn read_world<'a>(&mut self, world: &'a World) -> impl Iterator<Item = &'a T> + 'a
+    where
+        T: 'static,
+    {
+        world
+            .get_resource::<Events<T>>()
+            .map(|events| self.read(events))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Skips every unread event.
+    pub fn clear(&mut self, events: &Events<T>) {
+        self.last_event_count = events.event_count;
+    }
+}
+
+/// Swaps the buffers of `Events<T>` at the beginning of every frame.
+pub struct EventUpdater<T>(PhantomData<fn() -> T>);
+
+impl<T> std::fmt::Debug for EventUpdater<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("EventUpdater")
+            .field(&std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+impl<T> Default for EventUpdater<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: 'static> System for EventUpdater<T> {
+    fn run(&mut self, _context: &Context, world: &mut World) {
+        if let Some(events) = world.get_resource_mut::<Events<T>>() {
+            events.update();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(reader: &mut EventReader<u32>, events: &Events<u32>) -> Vec<u32> {
+        reader.read(events).copied().collect()
+    }
+
+    #[test]
+    fn events_survive_one_update() {
+        let mut events = Events::default();
+        events.send(1);
+        events.send(2);
+
+        events.update();
+        assert_eq!(events.len(), 2);
+
+        events.send(3);
+        events.update();
+        assert_eq!(events.len(), 1);
+
+        events.update();
+        assert!(events.is_empty());
+        assert_eq!(events.event_count(), 3);
+    }
+
+    #[test]
+    fn readers_see_every_event_once() {
+        let mut events = Events::default();
+        let mut early = EventReader::default();
+        let mut late = EventReader::default();
+
+        events.send(1);
+        assert_eq!(read(&mut early, &events), [1]);
+        assert!(read(&mut early, &events).is_empty());
+
+        // A reader running after the update still gets the events of the last frame
+        events.update();
+        events.send(2);
+        assert_eq!(read(&mut late, &events), [1, 2]);
+        assert_eq!(read(&mut early, &events), [2]);
+
+        events.update();
+        events.update();
+        events.send(3);
+        let mut skipping = EventReader::default();
+        skipping.clear(&events);
+        assert!(read(&mut skipping, &events).is_empty());
+        assert_eq!(read(&mut late, &events), [3]);
+    }
+}
@@ -0,0 +1,19 @@
+pub mod entity;
+pub mod event;
+pub mod query;
+pub mod resource;
+pub mod storage;
+pub mod world;
+
+pub use entity::Entity;
+pub use event::{EventReader, Events};
+pub use query::{Added, Changed, Query, ReadOnlyWorldQuery, WorldQuery};
+pub use resource::Resources;
+pub use storage::Mut;
+pub use world::{EntityMut, World};
+
+/// Marker for types that can be attached to an [`Entity`].
+/// Components are `Send` so the render world can be handed to the render thread.
+pub trait Component: Send + 'static {}
+
+impl<T: Send + 'static> Component for T {}
@@ -0,0 +1,366 @@
+use std::{
+    cell::{Ref, RefMut},
+    marker::PhantomData,
+};
+
+use super::{
+    entity::Entities,
+    storage::{ComponentStorage, Mut},
+    Component, Entity, World,
+};
+
+/// Change ticks a query compares the component ticks against.
+#[derive(Debug, Clone, Copy)]
+pub struct Ticks {
+    pub last_change_tick: u32,
+    pub change_tick: u32,
+}
+
+/// Something that can be fetched from the [`World`] per entity,
+/// implemented for `&T`, `&mut T`, [`Entity`], [`Changed`], [`Added`], `Option` and tuples.
+///
+/// Requesting the same component mutably twice in one query panics,
+/// the storages are borrowed at runtime.
+pub trait WorldQuery {
+    type Fetch<'w>;
+    type Item<'f>;
+
+    fn fetch(world: &World) -> Self::Fetch<'_>;
+
+    /// Returns `true` if the entity at `index` matches the query.
+    fn contains(fetch: &Self::Fetch<'_>, index: usize, ticks: Ticks) -> bool;
+
+    fn get<'f>(
+        fetch: &'f mut Self::Fetch<'_>,
+        index: usize,
+        ticks: Ticks,
+    ) -> Option<Self::Item<'f>>;
+}
+
+/// Queries that never hand out mutable access and can be iterated through a shared reference.
+pub trait ReadOnlyWorldQuery: WorldQuery {
+    fn get_ref<'f>(
+        fetch: &'f Self::Fetch<'_>,
+        index: usize,
+        ticks: Ticks,
+    ) -> Option<Self::Item<'f>>;
+}
+
+/// Query filter that only matches components mutated since the system last ran.
+pub struct Changed<T>(PhantomData<fn() -> T>);
+
+/// Query filter that only matches components added since the system last ran.
+pub struct Added<T>(PhantomData<fn() -> T>);
+
+impl<T: Component> WorldQuery for &T {
+    type Fetch<'w> = Option<Ref<'w, ComponentStorage<T>>>;
+    type Item<'f> = &'f T;
+
+    fn fetch(world: &World) -> Self::Fetch<'_> {
+        world.storage::<T>().map(|storage| storage.borrow())
+    }
+
+    fn contains(fetch: &Self::Fetch<'_>, index: usize, _ticks: Ticks) -> bool {
+        matches!(fetch, Some(storage) if storage.get(index).is_some())
+    }
+
+    fn get<'f>(
+        fetch: &'f mut Self::Fetch<'_>,
+        index: usize,
+        ticks: Ticks,
+    ) -> Option<Self::Item<'f>> {
+        Self::get_ref(fetch, index, ticks)
+    }
+}
+
+impl<T: Component> ReadOnlyWorldQuery for &T {
+    fn get_ref<'f>(
+        fetch: &'f Self::Fetch<'_>,
+        index: usize,
+        _ticks: Ticks,
+    ) -> Option<Self::Item<'f>> {
+        fetch.as_ref()?.get(index).map(|slot| &slot.value)
+    }
+}
+
+impl<T: Component> WorldQuery for &mut T {
+    type Fetch<'w> = Option<RefMut<'w, ComponentStorage<T>>>;
+    type Item<'f> = Mut<'f, T>;
+
+    fn fetch(world: &World) -> Self::Fetch<'_> {
+        world.storage::<T>().map(|storage| storage.borrow_mut())
+    }
+
+    fn contains(fetch: &Self::Fetch<'_>, index: usize, _ticks: Ticks) -> bool {
+        matches!(fetch, Some(storage) if storage.get(index).is_some())
+    }
+
+    fn get<'f>(
+        fetch: &'f mut Self::Fetch<'_>,
+        index: usize,
+        ticks: Ticks,
+    ) -> Option<Self::Item<'f>> {
+        fetch
+            .as_mut()?
+            .get_mut(index)
+            .map(|slot| Mut::new(slot, ticks.change_tick))
+    }
+}
+
+impl<T: Component> WorldQuery for Changed<T> {
+    type Fetch<'w> = Option<Ref<'w, ComponentStorage<T>>>;
+    type Item<'f> = &'f T;
+
+    fn fetch(world: &World) -> Self::Fetch<'_> {
+        world.storage::<T>().map(|storage| storage.borrow())
+    }
+
+    fn contains(fetch: &Self::Fetch<'_>, index: usize, ticks: Ticks) -> bool {
+        matches!(
+            fetch,
+            Some(storage) if storage
+                .get(index)
+                .is_some_and(|slot| slot.ticks.is_changed(ticks.last_change_tick))
+        )
+    }
+
+    fn get<'f>(
+        fetch: &'f mut Self::Fetch<'_>,
+        index: usize,
+        ticks: Ticks,
+    ) -> Option<Self::Item<'f>> {
+        Self::get_ref(fetch, index, ticks)
+    }
+}
+
+impl<T: Component> ReadOnlyWorldQuery for Changed<T> {
+    fn get_ref<'f>(
+        fetch: &'f Self::Fetch<'_>,
+        index: usize,
+        ticks: Ticks,
+    ) -> Option<Self::Item<'f>> {
+        fetch
+            .as_ref()?
+            .get(index)
+            .filter(|slot| slot.ticks.is_changed(ticks.last_change_tick))
+            .map(|slot| &slot.value)
+    }
+}
+
+impl<T: Component> WorldQuery for Added<T> {
+    type Fetch<'w> = Option<Ref<'w, ComponentStorage<T>>>;
+    type Item<'f> = &'f T;
+
+    fn fetch(world: &World) -> Self::Fetch<'_> {
+        world.storage::<T>().map(|storage| storage.borrow())
+    }
+
+    fn contains(fetch: &Self::Fetch<'_>, index: usize, ticks: Ticks) -> bool {
+        matches!(
+            fetch,
+            Some(storage) if storage
+                .get(index)
+                .is_some_and(|slot| slot.ticks.is_added(ticks.last_change_tick))
+        )
+    }
+
+    fn get<'f>(
+        fetch: &'f mut Self::Fetch<'_>,
+        index: usize,
+        ticks: Ticks,
+    ) -> Option<Self::Item<'f>> {
+        Self::get_ref(fetch, index, ticks)
+    }
+}
+
+impl<T: Component> ReadOnlyWorldQuery for Added<T> {
+    fn get_ref<'f>(
+        fetch: &'f Self::Fetch<'_>,
+        index: usize,
+        ticks: Ticks,
+    ) -> Option<Self::Item<'f>> {
+        fetch
+            .as_ref()?
+            .get(index)
+            .filter(|slot| slot.ticks.is_added(ticks.last_change_tick))
+            .map(|slot| &slot.value)
+    }
+}
+
+impl WorldQuery for Entity {
+    type Fetch<'w> = &'w Entities;
+    type Item<'f> = Entity;
+
+    fn fetch(world: &World) -> Self::Fetch<'_> {
+        world.entities()
+    }
+
+    fn contains(fetch: &Self::Fetch<'_>, index: usize, _ticks: Ticks) -> bool {
+        fetch.get(index).is_some()
+    }
+
+    fn get<'f>(
+        fetch: &'f mut Self::Fetch<'_>,
+        index: usize,
+        _ticks: Ticks,
+    ) -> Option<Self::Item<'f>> {
+        fetch.get(index)
+    }
+}
+
+impl ReadOnlyWorldQuery for Entity {
+    fn get_ref<'f>(
+        fetch: &'f Self::Fetch<'_>,
+        index: usize,
+        _ticks: Ticks,
+    ) -> Option<Self::Item<'f>> {
+        fetch.get(index)
+    }
+}
+
+impl<Q: WorldQuery> WorldQuery for Option<Q> {
+    type Fetch<'w> = Q::Fetch<'w>;
+    type Item<'f> = Option<Q::Item<'f>>;
+
+    fn fetch(world: &World) -> Self::Fetch<'_> {
+        Q::fetch(world)
+    }
+
+    fn contains(_fetch: &Self::Fetch<'_>, _index: usize, _ticks: Ticks) -> bool {
+        true
+    }
+
+    fn get<'f>(
+        fetch: &'f mut Self::Fetch<'_>,
+        index: usize,
+        ticks: Ticks,
+    ) -> Option<Self::Item<'f>> {
+        Some(Q::get(fetch, index, ticks))
+    }
+}
+
+impl<Q: ReadOnlyWorldQuery> ReadOnlyWorldQuery for Option<Q> {
+    fn get_ref<'f>(
+        fetch: &'f Self::Fetch<'_>,
+        index: usize,
+        ticks: Ticks,
+    ) -> Option<Self::Item<'f>> {
+        Some(Q::get_ref(fetch, index, ticks))
+    }
+}
+
+macro_rules! impl_tuple_query {
+    ($($name: ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($name: WorldQuery),*> WorldQuery for ($($name,)*) {
+            type Fetch<'w> = ($($name::Fetch<'w>,)*);
+            type Item<'f> = ($($name::Item<'f>,)*);
+
+            fn fetch(world: &World) -> Self::Fetch<'_> {
+                ($($name::fetch(world),)*)
+            }
+
+            fn contains(fetch: &Self::Fetch<'_>, index: usize, ticks: Ticks) -> bool {
+                let ($($name,)*) = fetch;
+                $($name::contains($name, index, ticks))&&*
+            }
+
+            fn get<'f>(
+                fetch: &'f mut Self::Fetch<'_>,
+                index: usize,
+                ticks: Ticks,
+            ) -> Option<Self::Item<'f>> {
+                let ($($name,)*) = fetch;
+                Some(($($name::get($name, index, ticks)?,)*))
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<$($name: ReadOnlyWorldQuery),*> ReadOnlyWorldQuery for ($($name,)*) {
+            fn get_ref<'f>(
+                fetch: &'f Self::Fetch<'_>,
+                index: usize,
+                ticks: Ticks,
+            ) -> Option<Self::Item<'f>> {
+                let ($($name,)*) = fetch;
+                Some(($($name::get_ref($name, index, ticks)?,)*))
+            }
+        }
+    };
+}
+
+impl_tuple_query!(A);
+impl_tuple_query!(A, B);
+impl_tuple_query!(A, B, C);
+impl_tuple_query!(A, B, C, D);
+impl_tuple_query!(A, B, C, D, E);
+impl_tuple_query!(A, B, C, D, E, F);
+
+/// Borrowed view over all entities matching `Q`.
+pub struct Query<'w, Q: WorldQuery> {
+    fetch: Q::Fetch<'w>,
+    entities: &'w Entities,
+    ticks: Ticks,
+}
+
+impl<'w, Q: WorldQuery> Query<'w, Q> {
+    pub(crate) fn new(world: &'w World, ticks: Ticks) -> Self {
+        Self {
+            fetch: Q::fetch(world),
+            entities: world.entities(),
+            ticks,
+        }
+    }
+
+    pub fn for_each(&mut self, mut f: impl FnMut(Q::Item<'_>)) {
+        for index in 0..self.entities.capacity() {
+            if self.entities.get(index).is_none() {
+                continue;
+            }
+
+            if let Some(item) = Q::get(&mut self.fetch, index, self.ticks) {
+                f(item);
+            }
+        }
+    }
+
+    pub fn get(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        if !self.entities.contains(entity) {
+            return None;
+        }
+
+        Q::get(&mut self.fetch, entity.index(), self.ticks)
+    }
+
+    /// Returns the first matching entity, handy for queries that expect a single result like the active camera.
+    pub fn single(&mut self) -> Option<Q::Item<'_>> {
+        let index = (0..self.entities.capacity()).find(|&index| {
+            self.entities.get(index).is_some() && Q::contains(&self.fetch, index, self.ticks)
+        })?;
+
+        Q::get(&mut self.fetch, index, self.ticks)
+    }
+
+    pub fn count(&self) -> usize {
+        (0..self.entities.capacity())
+            .filter(|&index| {
+                self.entities.get(index).is_some() && Q::contains(&self.fetch, index, self.ticks)
+            })
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+}
+
+impl<'w, Q: ReadOnlyWorldQuery> Query<'w, Q> {
+    /// Iterates the matches through a shared borrow, the items live as long as the query.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = Q::Item<'_>> + '_> {
+        Box::new(
+            (0..self.entities.capacity())
+                .filter(|&index| self.entities.get(index).is_some())
+                .filter_map(|index| Q::get_ref(&self.fetch, index, self.ticks)),
+        )
+    }
+}
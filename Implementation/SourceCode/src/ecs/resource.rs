@@ -0,0 +1,58 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+/// Type map of singletons shared between systems, e.g. [`Time`](crate::time::Time)
+/// or the [`AssetServer`](crate::assets::AssetServer).
+#[derive(Default)]
+pub struct Resources {
+    resources: HashMap<TypeId, Box<dyn Any + Send>>,
+}
+
+impl std::fmt::Debug for Resources {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Resources")
+            .field("len", &self.resources.len())
+            .finish()
+    }
+}
+
+impl Resources {
+    /// Inserts the resource, returning the previous value of the same type.
+    pub fn insert<T: Send + 'static>(&mut self, resource: T) -> Option<T> {
+        self.resources
+            .insert(TypeId::of::<T>(), Box::new(resource))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .and_then(|resource| resource.downcast().ok())
+            .map(|resource| *resource)
+    }
+
+    #[inline]
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .and_then(|resource| resource.downcast_ref())
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.resources
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|resource| resource.downcast_mut())
+    }
+
+    /// Moves every resource of `other` into these, replacing the resources of the same type.
+    pub fn append(&mut self, other: &mut Resources) {
+        self.resources.extend(other.resources.drain());
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    ops::{Deref, DerefMut},
+};
+
+use super::Component;
+
+/// Tick at which a component was added and last mutated.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ComponentTicks {
+    pub added: u32,
+    pub changed: u32,
+}
+
+impl ComponentTicks {
+    fn new(tick: u32) -> Self {
+        Self {
+            added: tick,
+            changed: tick,
+        }
+    }
+
+    #[inline]
+    pub fn is_added(&self, last_change_tick: u32) -> bool {
+        self.added > last_change_tick
+    }
+
+    #[inline]
+    pub fn is_changed(&self, last_change_tick: u32) -> bool {
+        self.changed > last_change_tick
+    }
+}
+
+#[derive(Debug)]
+pub struct Slot<T> {
+    pub value: T,
+    pub ticks: ComponentTicks,
+}
+
+/// Sparse storage of one component type, indexed by [`Entity::index`](super::Entity::index).
+#[derive(Debug)]
+pub struct ComponentStorage<T> {
+    slots: Vec<Option<Slot<T>>>,
+}
+
+impl<T> Default for ComponentStorage<T> {
+    fn default() -> Self {
+        Self { slots: vec![] }
+    }
+}
+
+impl<T> ComponentStorage<T> {
+    pub fn insert(&mut self, index: usize, value: T, tick: u32) {
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+
+        self.slots[index] = Some(Slot {
+            value,
+            ticks: ComponentTicks::new(tick),
+        });
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        self.slots
+            .get_mut(index)
+            .and_then(Option::take)
+            .map(|slot| slot.value)
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&Slot<T>> {
+        self.slots.get(index).and_then(Option::as_ref)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Slot<T>> {
+        self.slots.get_mut(index).and_then(Option::as_mut)
+    }
+}
+
+/// Type erased access to a `RefCell<ComponentStorage<T>>`.
+pub trait AnyStorage: Send {
+    fn remove_entity(&mut self, index: usize);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Component> AnyStorage for RefCell<ComponentStorage<T>> {
+    fn remove_entity(&mut self, index: usize) {
+        self.get_mut().remove(index);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Mutable access to a component that marks it as changed when it is written to.
+#[derive(Debug)]
+pub struct Mut<'a, T> {
+    value: &'a mut T,
+    ticks: &'a mut ComponentTicks,
+    change_tick: u32,
+}
+
+impl<'a, T> Mut<'a, T> {
+    pub(crate) fn new(slot: &'a mut Slot<T>, change_tick: u32) -> Self {
+        Self {
+            value: &mut slot.value,
+            ticks: &mut slot.ticks,
+            change_tick,
+        }
+    }
+
+    pub fn is_added(&self, last_change_tick: u32) -> bool {
+        self.ticks.is_added(last_change_tick)
+    }
+
+    pub fn is_changed(&self, last_change_tick: u32) -> bool {
+        self.ticks.is_changed(last_change_tick)
+    }
+
+    /// Returns the inner reference without flagging the component as changed.
+    pub fn bypass_change_detection(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<'a, T> Deref for Mut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'a, T> DerefMut for Mut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.ticks.changed = self.change_tick;
+        self.value
+    }
+}
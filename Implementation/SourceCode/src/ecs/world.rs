@@ -0,0 +1,380 @@
+use std::{
+    any::TypeId,
+    cell::{Ref, RefCell},
+    collections::HashMap,
+};
+
+use super::{
+    entity::Entities,
+    event::Events,
+    query::{Query, Ticks, WorldQuery},
+    resource::Resources,
+    storage::{AnyStorage, ComponentStorage, Mut},
+    Component, Entity,
+};
+
+/// Entity component store owned by the [`App`](crate::app::App) and handed to every system,
+/// together with the [`Resources`] shared between the systems.
+///
+/// Components are kept in one sparse storage per type. Every system run advances
+/// the change tick, [`Changed`](super::Changed) and [`Added`](super::Added)
+/// compare against the tick the current system ran at the last time.
+pub struct World {
+    entities: Entities,
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+    change_tick: u32,
+    last_change_tick: u32,
+    resources: Resources,
+}
+
+impl std::fmt::Debug for World {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("World")
+            .field("entities", &self.entities.len())
+            .field("component_types", &self.storages.len())
+            .field("change_tick", &self.change_tick)
+            .field("resources", &self.resources)
+            .finish()
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self {
+            entities: Entities::default(),
+            storages: HashMap::new(),
+            change_tick: 1,
+            last_change_tick: 0,
+            resources: Resources::default(),
+        }
+    }
+}
+
+impl World {
+    pub fn new() -> Self {
+        World::default()
+    }
+
+    pub fn spawn(&mut self) -> EntityMut<'_> {
+        let entity = self.entities.alloc();
+        EntityMut {
+            world: self,
+            entity,
+        }
+    }
+
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.entities.free(entity) {
+            return false;
+        }
+
+        self.storages
+            .values_mut()
+            .for_each(|storage| storage.remove_entity(entity.index()));
+
+        true
+    }
+
+    #[inline]
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(entity)
+    }
+
+    pub fn entity_mut(&mut self, entity: Entity) -> Option<EntityMut<'_>> {
+        match self.contains(entity) {
+            true => Some(EntityMut {
+                world: self,
+                entity,
+            }),
+            false => None,
+        }
+    }
+
+    /// Adds or replaces the component `T` of the entity. Returns `false` if the entity is despawned.
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) -> bool {
+        if !self.contains(entity) {
+            return false;
+        }
+
+        let tick = self.change_tick;
+        self.storage_mut::<T>()
+            .insert(entity.index(), component, tick);
+
+        true
+    }
+
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        if !self.contains(entity) {
+            return None;
+        }
+
+        self.storages
+            .get_mut(&TypeId::of::<T>())?
+            .as_any_mut()
+            .downcast_mut::<RefCell<ComponentStorage<T>>>()?
+            .get_mut()
+            .remove(entity.index())
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<Ref<'_, T>> {
+        if !self.contains(entity) {
+            return None;
+        }
+
+        let storage = self.storage::<T>()?.borrow();
+        Ref::filter_map(storage, |storage| {
+            storage.get(entity.index()).map(|slot| &slot.value)
+        })
+        .ok()
+    }
+
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<Mut<'_, T>> {
+        if !self.contains(entity) {
+            return None;
+        }
+
+        let change_tick = self.change_tick;
+        self.storages
+            .get_mut(&TypeId::of::<T>())?
+            .as_any_mut()
+            .downcast_mut::<RefCell<ComponentStorage<T>>>()?
+            .get_mut()
+            .get_mut(entity.index())
+            .map(|slot| Mut::new(slot, change_tick))
+    }
+
+    /// ```ignore
+    /// world
+    ///     .query::<(Entity, &Camera, &mut Transform)>()
+    ///     .for_each(|(entity, camera, mut transform)| { /* ... */ });
+    /// ```
+    pub fn query<Q: WorldQuery>(&self) -> Query<'_, Q> {
+        Query::new(
+            self,
+            Ticks {
+                last_change_tick: self.last_change_tick,
+                change_tick: self.change_tick,
+            },
+        )
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Inserts the resource, returning the previous value of the same type.
+    pub fn insert_resource<T: Send + 'static>(&mut self, resource: T) -> Option<T> {
+        self.resources.insert(resource)
+    }
+
+    pub fn remove_resource<T: 'static>(&mut self) -> Option<T> {
+        self.resources.remove::<T>()
+    }
+
+    #[inline]
+    pub fn contains_resource<T: 'static>(&self) -> bool {
+        self.resources.contains::<T>()
+    }
+
+    #[inline]
+    pub fn get_resource<T: 'static>(&self) -> Option<&T> {
+        self.resources.get::<T>()
+    }
+
+    #[inline]
+    pub fn get_resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.resources.get_mut::<T>()
+    }
+
+    /// Moves every resource of `other` into this world, replacing the resources of the same type.
+    pub fn append_resources(&mut self, other: &mut World) {
+        self.resources.append(&mut other.resources);
+    }
+
+    /// Sends the event to the `Events<T>` resource, returns `false` if `T` was never registered.
+    pub fn send_event<T: Send + 'static>(&mut self, event: T) -> bool {
+        match self.get_resource_mut::<Events<T>>() {
+            Some(events) => {
+                events.send(event);
+                true
+            }
+            None => {
+                log::warn!("{} was never added as event", std::any::type_name::<T>());
+                false
+            }
+        }
+    }
+
+    #[inline]
+    pub fn change_tick(&self) -> u32 {
+        self.change_tick
+    }
+
+    #[inline]
+    pub fn last_change_tick(&self) -> u32 {
+        self.last_change_tick
+    }
+
+    /// Runs `f` as a system that last ran at `*last_run` and advances the change tick afterwards.
+    pub(crate) fn run_system<R>(
+        &mut self,
+        last_run: &mut u32,
+        f: impl FnOnce(&mut World) -> R,
+    ) -> R {
+        self.last_change_tick = *last_run;
+        let result = f(self);
+
+        *last_run = self.change_tick;
+        self.change_tick += 1;
+
+        result
+    }
+
+    #[inline]
+    pub(crate) fn entities(&self) -> &Entities {
+        &self.entities
+    }
+
+    pub(crate) fn storage<T: Component>(&self) -> Option<&RefCell<ComponentStorage<T>>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .and_then(|storage| storage.as_any().downcast_ref())
+    }
+
+    fn storage_mut<T: Component>(&mut self) -> &mut ComponentStorage<T> {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(RefCell::new(ComponentStorage::<T>::default())))
+            .as_any_mut()
+            .downcast_mut::<RefCell<ComponentStorage<T>>>()
+            .unwrap()
+            .get_mut()
+    }
+}
+
+/// Builder returned by [`World::spawn`] to attach components to a new entity.
+pub struct EntityMut<'w> {
+    world: &'w mut World,
+    entity: Entity,
+}
+
+impl<'w> EntityMut<'w> {
+    #[inline]
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn insert<T: Component>(&mut self, component: T) -> &mut Self {
+        self.world.insert(self.entity, component);
+        self
+    }
+
+    pub fn remove<T: Component>(&mut self) -> Option<T> {
+        self.world.remove::<T>(self.entity)
+    }
+
+    pub fn despawn(self) {
+        self.world.despawn(self.entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::{Added, Changed};
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Debug, PartialEq)]
+    struct Velocity(i32);
+
+    #[test]
+    fn respawned_entities_get_a_new_generation() {
+        let mut world = World::new();
+        let first = world.spawn().insert(Position(1)).id();
+
+        assert!(world.despawn(first));
+        assert!(!world.despawn(first));
+        assert!(!world.contains(first));
+        assert!(world.get::<Position>(first).is_none());
+
+        let second = world.spawn().id();
+        assert_eq!(second.index(), first.index());
+        assert_eq!(second.generation(), first.generation() + 1);
+
+        // The stale handle neither sees nor touches the new entity
+        assert!(!world.insert(first, Position(2)));
+        assert!(world.get::<Position>(second).is_none());
+        assert_eq!(world.len(), 1);
+    }
+
+    #[test]
+    fn queries_match_entities_with_every_component() {
+        let mut world = World::new();
+        let still = world.spawn().insert(Position(1)).id();
+        let moving = world.spawn().insert(Position(2)).insert(Velocity(3)).id();
+        world.spawn().insert(Velocity(4));
+
+        let matches: Vec<_> = world
+            .query::<(Entity, &Position, &Velocity)>()
+            .iter()
+            .map(|(entity, _, _)| entity)
+            .collect();
+        assert_eq!(matches, [moving]);
+        assert_eq!(world.query::<(&Position, Option<&Velocity>)>().count(), 2);
+
+        world
+            .query::<(&mut Position, &Velocity)>()
+            .for_each(|(mut position, velocity)| {
+                position.0 += velocity.0;
+            });
+        assert_eq!(*world.get::<Position>(moving).unwrap(), Position(5));
+        assert_eq!(*world.get::<Position>(still).unwrap(), Position(1));
+
+        world.despawn(moving);
+        assert!(world
+            .query::<&Velocity>()
+            .iter()
+            .all(|velocity| velocity.0 == 4));
+    }
+
+    #[test]
+    fn changes_are_detected_once_per_system() {
+        let mut world = World::new();
+        let entity = world.spawn().insert(Position(1)).id();
+        let mut last_run = 0;
+
+        let detect = |world: &mut World, last_run: &mut u32| {
+            world.run_system(last_run, |world| {
+                (
+                    world.query::<Added<Position>>().count(),
+                    world.query::<Changed<Position>>().count(),
+                )
+            })
+        };
+
+        assert_eq!(detect(&mut world, &mut last_run), (1, 1));
+        assert_eq!(detect(&mut world, &mut last_run), (0, 0));
+
+        // Reading through `Mut` or bypassing the detection doesn't count as a change
+        let mut position = world.get_mut::<Position>(entity).unwrap();
+        assert_eq!(position.0, 1);
+        position.bypass_change_detection().0 = 2;
+        assert_eq!(detect(&mut world, &mut last_run), (0, 0));
+
+        world.get_mut::<Position>(entity).unwrap().0 = 3;
+        assert_eq!(detect(&mut world, &mut last_run), (0, 1));
+        assert_eq!(detect(&mut world, &mut last_run), (0, 0));
+
+        // Another system that never ran sees the component as added
+        let mut other_last_run = 0;
+        assert_eq!(detect(&mut world, &mut other_last_run), (1, 1));
+    }
+}
@@ -0,0 +1,211 @@
+//! Golden-image tests: scenes are rendered offscreen and compared with reference PNGs in `tests/golden`.
+//!
+//! A missing reference fails the test, set `GOLDEN_UPDATE=1` to write the references from the current
+//! renders after an intended change, then review and commit them.
+//! On a mismatch the render and a diff image are written to `target/golden`.
+
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    app::{App, AppError, AppOptions},
+    loader::{AnimationLoop, GltfViewerPlugin},
+    ASSETS_PATH,
+};
+
+const REFERENCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+const OUTPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/golden");
+
+/// How far a render may drift from its reference.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    /// Largest difference of a color channel for a pixel to still match.
+    pub per_channel: u8,
+    /// Pixels allowed to exceed `per_channel`, covers rasterization differences at edges.
+    pub max_mismatched_pixels: usize,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            per_channel: 2,
+            max_mismatched_pixels: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Comparison {
+    pub mismatched_pixels: usize,
+    pub max_difference: u8,
+    /// Mismatched pixels in red scaled by their difference, matching pixels as dimmed grayscale.
+    pub diff: RgbaImage,
+}
+
+/// Compares two images of the same size pixel by pixel.
+pub fn compare(actual: &RgbaImage, expected: &RgbaImage, tolerance: Tolerance) -> Comparison {
+    assert_eq!(
+        actual.dimensions(),
+        expected.dimensions(),
+        "the render and the reference differ in size"
+    );
+
+    let mut mismatched_pixels = 0;
+    let mut max_difference = 0;
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+
+    for ((actual, expected), diff) in actual
+        .pixels()
+        .zip(expected.pixels())
+        .zip(diff.pixels_mut())
+    {
+        let difference = actual
+            .0
+            .iter()
+            .zip(expected.0.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0);
+        max_difference = max_difference.max(difference);
+
+        *diff = match difference > tolerance.per_channel {
+            true => {
+                mismatched_pixels += 1;
+                Rgba([128 + difference / 2, 0, 0, 255])
+            }
+            false => {
+                let [r, g, b, _] = expected.0;
+                let luma = ((r as u32 + g as u32 + b as u32) / 3 / 4) as u8;
+                Rgba([luma, luma, luma, 255])
+            }
+        };
+    }
+
+    Comparison {
+        mismatched_pixels,
+        max_difference,
+        diff,
+    }
+}
+
+/// Compares `actual` with the reference `tests/golden/<name>.png`, panicking with the paths
+/// of the written render and diff image if they differ by more than the tolerance.
+pub fn assert_matches_reference(name: &str, actual: &RgbaImage, tolerance: Tolerance) {
+    let reference_path = Path::new(REFERENCE_DIR).join(format!("{name}.png"));
+    let update = std::env::var_os("GOLDEN_UPDATE").is_some_and(|value| value == "1");
+
+    if update {
+        save(&reference_path, actual);
+        return;
+    }
+    assert!(
+        reference_path.exists(),
+        "the reference `{}` is missing, write it with `GOLDEN_UPDATE=1`, review and commit it",
+        reference_path.display()
+    );
+
+    let expected = image::open(&reference_path)
+        .unwrap_or_else(|error| panic!("failed to read `{}`: {error}", reference_path.display()))
+        .into_rgba8();
+    let comparison = compare(actual, &expected, tolerance);
+
+    if comparison.mismatched_pixels > tolerance.max_mismatched_pixels {
+        let actual_path = Path::new(OUTPUT_DIR).join(format!("{name}.actual.png"));
+        let diff_path = Path::new(OUTPUT_DIR).join(format!("{name}.diff.png"));
+        save(&actual_path, actual);
+        save(&diff_path, &comparison.diff);
+
+        panic!(
+            "`{name}` differs from its reference in {} pixels (allowed {}, largest channel difference {}), \
+             render: `{}`, diff: `{}`",
+            comparison.mismatched_pixels,
+            tolerance.max_mismatched_pixels,
+            comparison.max_difference,
+            actual_path.display(),
+            diff_path.display(),
+        );
+    }
+}
+
+/// Renders a model of the asset folder through the glTF viewer on a software adapter.
+/// Returns `None` if there is no adapter to render with.
+pub fn render_model(model: impl AsRef<Path>, size: PhysicalSize<u32>) -> Option<RgbaImage> {
+    let options = AppOptions {
+        force_fallback_adapter: true,
+        ..Default::default()
+    };
+
+    let path: PathBuf = Path::new(ASSETS_PATH).join(model);
+    let mut app = App::headless(options, size);
+    app.add_plugin(GltfViewerPlugin::new(
+        path.to_string_lossy(),
+        AnimationLoop::Disable,
+    ));
+
+    match app.render_headless() {
+        Ok(image) => Some(image),
+        Err(AppError::NoAdapter) => None,
+        Err(error) => panic!("failed to render `{}`: {error}", path.display()),
+    }
+}
+
+fn save(path: &Path, image: &RgbaImage) {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).expect("failed to create the golden image directory");
+    }
+    image
+        .save(path)
+        .unwrap_or_else(|error| panic!("failed to write `{}`: {error}", path.display()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_images_match() {
+        let image = RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255]));
+        let comparison = compare(&image, &image, Tolerance::default());
+
+        assert_eq!(comparison.mismatched_pixels, 0);
+        assert_eq!(comparison.max_difference, 0);
+    }
+
+    #[test]
+    fn differences_within_tolerance_match() {
+        let expected = RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255]));
+        let mut actual = expected.clone();
+        actual.put_pixel(1, 1, Rgba([12, 20, 29, 255]));
+        actual.put_pixel(2, 2, Rgba([40, 20, 30, 255]));
+
+        let comparison = compare(&actual, &expected, Tolerance::default());
+
+        assert_eq!(comparison.mismatched_pixels, 1);
+        assert_eq!(comparison.max_difference, 30);
+        assert_eq!(comparison.diff.get_pixel(2, 2).0[0], 128 + 15);
+        assert_eq!(
+            comparison.diff.get_pixel(1, 1).0[1],
+            comparison.diff.get_pixel(1, 1).0[0]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a software adapter, run with `cargo test -- --ignored`"]
+    fn cube_matches_reference() {
+        let Some(image) = render_model("models/cube/Cube.glb", PhysicalSize::new(256, 256)) else {
+            eprintln!("skipping `cube_matches_reference`, there is no adapter to render with");
+            return;
+        };
+
+        assert_matches_reference(
+            "cube",
+            &image,
+            Tolerance {
+                per_channel: 3,
+                max_mismatched_pixels: 64,
+            },
+        );
+    }
+}
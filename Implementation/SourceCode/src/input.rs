@@ -0,0 +1,411 @@
+//! Turns keyboard and mouse events into named actions and axes.
+//!
+//! Actions are buttons like `toggle_animation`, axes are values like `zoom` that sum up
+//! pairs of buttons, the mouse wheel or the mouse motion. Both are bound in an [`InputMap`],
+//! which can be loaded from the `input` section of the config:
+//!
+//! ```ron
+//! input: (
+//!     actions: {"toggle_animation": [Key(Space), Mouse(Middle)]},
+//!     axes: {"move_forward": [Buttons(negative: Key(S), positive: Key(W)), MouseWheel]},
+//! )
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use glam::Vec2;
+use serde::Deserialize;
+use winit::event::{
+    DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
+    WindowEvent,
+};
+
+use crate::{
+    app::{App, Context, System, World},
+    plugin::Plugin,
+    schedule::{Stage, SystemDescriptor, SystemLabel},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Button {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+/// An input driving an axis, the values of every binding of an axis are summed up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum AxisBinding {
+    /// -1 while `negative` is held and 1 while `positive` is held.
+    Buttons { negative: Button, positive: Button },
+    /// Lines scrolled in this frame, positive away from the user.
+    MouseWheel,
+    /// Pixels the mouse moved in this frame, positive to the right.
+    MouseMotionX,
+    /// Pixels the mouse moved in this frame, positive downwards.
+    MouseMotionY,
+}
+
+/// The buttons of every action and the bindings of every axis, keyed by their name.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct InputMap {
+    pub actions: HashMap<String, Vec<Button>>,
+    pub axes: HashMap<String, Vec<AxisBinding>>,
+}
+
+impl InputMap {
+    pub fn with_action(mut self, action: &str, buttons: impl Into<Vec<Button>>) -> Self {
+        self.bind_action(action, buttons);
+        self
+    }
+
+    pub fn with_axis(mut self, axis: &str, bindings: impl Into<Vec<AxisBinding>>) -> Self {
+        self.bind_axis(axis, bindings);
+        self
+    }
+
+    /// Replaces the buttons of `action`, no buttons leave it unbound.
+    pub fn bind_action(&mut self, action: &str, buttons: impl Into<Vec<Button>>) -> &mut Self {
+        self.actions.insert(action.to_owned(), buttons.into());
+        self
+    }
+
+    /// Replaces the bindings of `axis`, no bindings leave it at 0.
+    pub fn bind_axis(&mut self, axis: &str, bindings: impl Into<Vec<AxisBinding>>) -> &mut Self {
+        self.axes.insert(axis.to_owned(), bindings.into());
+        self
+    }
+
+    /// Rebinds every action and axis bound in `other`, the others keep their bindings.
+    pub fn merge(&mut self, other: InputMap) -> &mut Self {
+        other.actions.into_iter().for_each(|(action, buttons)| {
+            self.bind_action(&action, buttons);
+        });
+        other.axes.into_iter().for_each(|(axis, bindings)| {
+            self.bind_axis(&axis, bindings);
+        });
+        self
+    }
+}
+
+/// The state of the actions and axes in the current frame, updated in the `PreUpdate` stage by [`InputPlugin`].
+///
+/// Systems rebind at runtime through [`Input::bindings`], queries use the new bindings right away.
+#[derive(Debug, Default)]
+pub struct Input {
+    pub bindings: InputMap,
+    pressed: HashSet<Button>,
+    just_pressed: HashSet<Button>,
+    just_released: HashSet<Button>,
+    wheel: f32,
+    motion: Vec2,
+}
+
+impl Input {
+    pub fn new(bindings: InputMap) -> Self {
+        Self {
+            bindings,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a button of `action` is held.
+    pub fn pressed(&self, action: &str) -> bool {
+        self.any_button(action, &self.pressed)
+    }
+
+    /// Whether a button of `action` was pressed in this frame, key repeats don't count.
+    pub fn just_pressed(&self, action: &str) -> bool {
+        self.any_button(action, &self.just_pressed)
+    }
+
+    /// Whether a button of `action` was released in this frame.
+    pub fn just_released(&self, action: &str) -> bool {
+        self.any_button(action, &self.just_released)
+    }
+
+    /// The sum of the bindings of `axis`, 0 if it isn't bound.
+    pub fn axis(&self, axis: &str) -> f32 {
+        let bindings = match self.bindings.axes.get(axis) {
+            Some(bindings) => bindings,
+            None => return 0.0,
+        };
+
+        let held = |button: &Button| self.pressed.contains(button) as u8 as f32;
+        bindings
+            .iter()
+            .map(|binding| match binding {
+                AxisBinding::Buttons { negative, positive } => held(positive) - held(negative),
+                AxisBinding::MouseWheel => self.wheel,
+                AxisBinding::MouseMotionX => self.motion.x,
+                AxisBinding::MouseMotionY => self.motion.y,
+            })
+            .sum()
+    }
+
+    fn any_button(&self, action: &str, buttons: &HashSet<Button>) -> bool {
+        self.bindings
+            .actions
+            .get(action)
+            .is_some_and(|bound| bound.iter().any(|button| buttons.contains(button)))
+    }
+
+    /// Starts a new frame with the input received since the last one.
+    fn update(&mut self, received: ReceivedInput) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+
+        if received.release_all {
+            self.just_released.extend(self.pressed.drain());
+        }
+
+        for (button, state) in received.buttons {
+            match state {
+                ElementState::Pressed if self.pressed.insert(button) => {
+                    self.just_pressed.insert(button);
+                }
+                ElementState::Released if self.pressed.remove(&button) => {
+                    self.just_released.insert(button);
+                }
+                _ => {}
+            }
+        }
+
+        self.wheel = received.wheel;
+        self.motion = received.motion;
+    }
+}
+
+/// Adds the [`Input`] resource of the primary window.
+///
+/// Adding it again, e.g. from another plugin, rebinds what its bindings bind.
+#[derive(Debug, Clone, Default)]
+pub struct InputPlugin {
+    pub bindings: InputMap,
+}
+
+impl InputPlugin {
+    pub fn new(bindings: InputMap) -> Self {
+        Self { bindings }
+    }
+}
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(input) = app.get_resource_mut::<Input>() {
+            input.bindings.merge(self.bindings.clone());
+            return;
+        }
+
+        app.insert_resource(Input::new(self.bindings.clone()))
+            .add_system(
+                SystemDescriptor::new(InputUpdater::default())
+                    .in_stage(Stage::PreUpdate)
+                    .label(InputUpdater::LABEL),
+            );
+    }
+}
+
+/// Input received between two frames.
+#[derive(Debug, Default)]
+struct ReceivedInput {
+    buttons: Vec<(Button, ElementState)>,
+    /// The window lost the focus, it won't receive the releases of the held buttons.
+    release_all: bool,
+    wheel: f32,
+    motion: Vec2,
+}
+
+/// Collects the events of the window and applies them to [`Input`] at the start of the frame.
+#[derive(Debug, Default)]
+pub struct InputUpdater {
+    received: ReceivedInput,
+    is_focused: bool,
+}
+
+impl InputUpdater {
+    pub const LABEL: SystemLabel = "input_updater";
+    /// Lines scrolled per pixel of touchpads and precise wheels.
+    const LINES_PER_PIXEL: f32 = 1.0 / 20.0;
+}
+
+impl System for InputUpdater {
+    fn init(&mut self, context: &Context, _world: &mut World) {
+        // Windows are created focused, winit only reports changes
+        self.is_focused = context.window_id().is_some();
+    }
+
+    fn process_events(&mut self, event: &Event<()>, context: &Context, _world: &mut World) {
+        let received = &mut self.received;
+
+        match event {
+            Event::WindowEvent { event, window_id } if Some(*window_id) == context.window_id() => {
+                match event {
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(keycode),
+                                state,
+                                ..
+                            },
+                        ..
+                    } => received.buttons.push((Button::Key(*keycode), *state)),
+                    WindowEvent::MouseInput { state, button, .. } => {
+                        received.buttons.push((Button::Mouse(*button), *state))
+                    }
+                    WindowEvent::MouseWheel { delta, .. } => {
+                        received.wheel += match delta {
+                            MouseScrollDelta::LineDelta(_, y) => *y,
+                            MouseScrollDelta::PixelDelta(position) => {
+                                position.y as f32 * InputUpdater::LINES_PER_PIXEL
+                            }
+                        }
+                    }
+                    WindowEvent::Focused(is_focused) => {
+                        self.is_focused = *is_focused;
+                        if !is_focused {
+                            received.buttons.clear();
+                            received.release_all = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            // Raw motion keeps coming while the cursor is grabbed, but isn't tied to a window
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta: (x, y) },
+                ..
+            } if self.is_focused => received.motion += Vec2::new(*x as f32, *y as f32),
+            _ => {}
+        }
+    }
+
+    fn run(&mut self, _context: &Context, world: &mut World) {
+        if let Some(input) = world.get_resource_mut::<Input>() {
+            input.update(std::mem::take(&mut self.received));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACE: Button = Button::Key(VirtualKeyCode::Space);
+    const W: Button = Button::Key(VirtualKeyCode::W);
+    const S: Button = Button::Key(VirtualKeyCode::S);
+    const LEFT: Button = Button::Mouse(MouseButton::Left);
+
+    fn input() -> Input {
+        Input::new(
+            InputMap::default()
+                .with_action("jump", [SPACE, LEFT])
+                .with_axis(
+                    "forward",
+                    [
+                        AxisBinding::Buttons {
+                            negative: S,
+                            positive: W,
+                        },
+                        AxisBinding::MouseWheel,
+                    ],
+                ),
+        )
+    }
+
+    fn buttons(buttons: &[(Button, ElementState)]) -> ReceivedInput {
+        ReceivedInput {
+            buttons: buttons.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn edges_last_one_frame() {
+        let mut input = input();
+
+        input.update(buttons(&[(SPACE, ElementState::Pressed)]));
+        assert!(input.pressed("jump") && input.just_pressed("jump"));
+
+        input.update(ReceivedInput::default());
+        assert!(input.pressed("jump") && !input.just_pressed("jump"));
+
+        input.update(buttons(&[(SPACE, ElementState::Released)]));
+        assert!(!input.pressed("jump") && input.just_released("jump"));
+
+        input.update(ReceivedInput::default());
+        assert!(!input.just_released("jump"));
+        assert!(!input.pressed("unbound") && !input.just_pressed("unbound"));
+    }
+
+    #[test]
+    fn key_repeats_are_no_presses() {
+        let mut input = input();
+        input.update(buttons(&[(SPACE, ElementState::Pressed)]));
+
+        input.update(buttons(&[
+            (SPACE, ElementState::Pressed),
+            (SPACE, ElementState::Pressed),
+        ]));
+        assert!(input.pressed("jump") && !input.just_pressed("jump"));
+
+        // A release without a press, e.g. of a key held before the window got the focus
+        input.update(buttons(&[(LEFT, ElementState::Released)]));
+        assert!(input.pressed("jump") && !input.just_released("jump"));
+    }
+
+    #[test]
+    fn losing_the_focus_releases_everything() {
+        let mut input = input();
+        input.update(buttons(&[
+            (SPACE, ElementState::Pressed),
+            (W, ElementState::Pressed),
+        ]));
+        assert_eq!(input.axis("forward"), 1.0);
+
+        input.update(ReceivedInput {
+            release_all: true,
+            ..Default::default()
+        });
+        assert!(!input.pressed("jump") && input.just_released("jump"));
+        assert_eq!(input.axis("forward"), 0.0);
+
+        // The presses after getting the focus back count again
+        input.update(buttons(&[(SPACE, ElementState::Pressed)]));
+        assert!(input.just_pressed("jump"));
+    }
+
+    #[test]
+    fn axes_sum_their_bindings() {
+        let mut input = input();
+
+        input.update(ReceivedInput {
+            buttons: vec![(W, ElementState::Pressed), (S, ElementState::Pressed)],
+            wheel: 2.0,
+            ..Default::default()
+        });
+        assert_eq!(input.axis("forward"), 2.0);
+
+        input.update(buttons(&[(W, ElementState::Released)]));
+        assert_eq!(input.axis("forward"), -1.0);
+        assert_eq!(input.axis("unbound"), 0.0);
+    }
+
+    #[test]
+    fn merging_rebinds_what_is_bound() {
+        let mut bindings = input().bindings;
+        bindings.merge(
+            InputMap::default()
+                .with_action("jump", [W])
+                .with_action("crouch", [S]),
+        );
+
+        assert_eq!(bindings.actions["jump"], [W]);
+        assert_eq!(bindings.actions["crouch"], [S]);
+        assert_eq!(bindings.axes["forward"].len(), 2);
+
+        // Binding nothing unbinds
+        bindings.merge(InputMap::default().with_axis("forward", []));
+        assert!(bindings.axes["forward"].is_empty());
+    }
+}
@@ -1,24 +1,35 @@
-use async_trait::async_trait;
 use gltf::mesh::Mode;
+use serde::Deserialize;
 use std::{
-    borrow::{Borrow, Cow},
-    collections::BTreeMap,
-    f32::{consts::PI, INFINITY},
-    ops::{Deref, Range},
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, HashSet},
+    fmt,
+    ops::Range,
+    str::FromStr,
+    sync::{
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
 };
 use thiserror::Error;
-use wgpu::{
-    util::DeviceExt, BlendComponent, DepthBiasState, DepthStencilState, Extent3d, Features, LoadOp,
-    Operations, PolygonMode, PrimitiveTopology, RenderPassDepthStencilAttachment, RenderPipeline,
-    StencilState, TextureDescriptor, TextureUsages, TextureViewDescriptor,
-};
+use wgpu::{util::DeviceExt, BlendComponent, Features, PolygonMode, PrimitiveTopology};
 use winit::{dpi::PhysicalSize, event::*};
 
 use crate::{
-    app::{Context, Resource, SharedContext, System},
+    app::{App, Context, System, World},
     camera::first::{CameraController, MyCamera},
+    ecs::Entity,
+    input::{AxisBinding, Button, Input, InputMap, InputPlugin},
     mesh::AABB,
+    pipeline::{DepthMode, DepthState, PipelineCache, PipelineKey},
+    plugin::Plugin,
+    render::MainWorld,
+    render_graph::{
+        Node, NodeBuilder, NodeContext, NodeLabel, RenderGraph, ResourceLabel, TextureInfo,
+    },
+    schedule::{Stage, SystemDescriptor, SystemLabel},
+    shaders::{ShaderDefs, ShaderError, Shaders},
+    time::Time,
     vertex::{Float32x2, Float32x3, ModelVertex, Vertex},
 };
 
@@ -140,19 +151,25 @@ impl From<Vec<[f32; 2]>> for AttributeValues {
     }
 }
 
+/// The camera component spawned by [`CameraUpdater`] and drawn with by [`GLTFRenderer`].
 #[derive(Debug)]
-struct CameraState {
+pub struct CameraState {
     // pub camera: Camera,
-    pub camera_buffer: wgpu::Buffer,
+    pub camera_buffer: Arc<wgpu::Buffer>,
     pub camera_uniform: CameraUniform,
     pub camera_controller: CameraController,
-    pub camera_bind_group: wgpu::BindGroup,
-    pub camera_bind_group_layout: wgpu::BindGroupLayout,
+    pub camera_bind_group: Arc<wgpu::BindGroup>,
+    pub camera_bind_group_layout: Arc<wgpu::BindGroupLayout>,
 }
 
 impl CameraState {
-    fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
-        let camera = MyCamera::new(config);
+    fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_mode: DepthMode,
+    ) -> Self {
+        let mut camera = MyCamera::new(config);
+        camera.depth_mode = depth_mode;
         let mut camera_controller = CameraController::new(camera, 0.4);
         let view = camera_controller.update_camera();
 
@@ -191,9 +208,9 @@ impl CameraState {
 
         CameraState {
             camera_uniform,
-            camera_buffer,
-            camera_bind_group,
-            camera_bind_group_layout,
+            camera_buffer: Arc::new(camera_buffer),
+            camera_bind_group: Arc::new(camera_bind_group),
+            camera_bind_group_layout: Arc::new(camera_bind_group_layout),
             camera_controller,
         }
     }
@@ -202,52 +219,44 @@ impl CameraState {
 #[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
 pub struct HandleId(usize);
 
-#[derive(Debug)]
-pub struct State {
-    pub size: winit::dpi::PhysicalSize<u32>,
-    camera_state: CameraState,
-    render_pipeline: Option<RenderPipeline>,
-    meshes: Option<Vec<MeshBuffer>>,
-}
+impl CameraState {
+    /// Updates the uniform, [`GLTFRenderer`] uploads it on the render thread.
+    fn update(&mut self, model: &glam::Mat4) {
+        self.camera_controller.update_camera();
 
-impl State {
-    fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> State {
-        let camera_state = CameraState::new(&device, &config);
+        self.camera_uniform
+            .update_view_proj(&self.camera_controller.camera, &model);
+    }
 
-        Self {
-            size: PhysicalSize::new(config.width, config.height),
-            camera_state,
-            render_pipeline: None,
-            meshes: None,
+    fn extract(&self) -> ExtractedCamera {
+        ExtractedCamera {
+            uniform: self.camera_uniform,
+            buffer: self.camera_buffer.clone(),
+            bind_group: self.camera_bind_group.clone(),
+            bind_group_layout: self.camera_bind_group_layout.clone(),
         }
     }
 
-    fn update(&mut self, context: &Context, model: &glam::Mat4) {
-        self.camera_state.camera_controller.update_camera();
-
-        self.camera_state
-            .camera_uniform
-            .update_view_proj(&self.camera_state.camera_controller.camera, &model);
-
-        context.queue.write_buffer(
-            &self.camera_state.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[self.camera_state.camera_uniform]),
-        );
+    pub fn animate(&mut self, delta: f32) {
+        self.camera_controller.animate(delta);
     }
 
-    pub fn animate(&mut self) {
-        let controller = &mut self.camera_state.camera_controller;
-        controller.animate();
+    fn apply_input(&mut self, input: &CameraInput) {
+        if input.zoom != 0.0 {
+            self.camera_controller.zoom(input.zoom);
+        }
+        self.camera_controller.move_by(input.forward, input.orbit);
     }
 
-    fn input(&mut self, event: &WindowEvent, context: &Context) -> bool {
-        self.camera_state
-            .camera_controller
-            .process_events(event, context)
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.camera_controller.camera.aspect = size.width as f32 / size.height as f32;
     }
 }
 
+/// Model matrix of the loaded meshes, derived from the bounding box of the first primitive.
+#[derive(Debug, Clone, Copy)]
+pub struct ModelTransform(pub glam::Mat4);
+
 #[repr(C)]
 // This is so we can store this in a buffer
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -256,7 +265,7 @@ pub struct Matrix4x4([[f32; 4]; 4]);
 #[repr(C)]
 // This is so we can store this in a buffer
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct CameraUniform {
+pub struct CameraUniform {
     // We can't use cgmath with bytemuck directly so we'll have
     // to convert the Matrix4 into a 4x4 f32 array
     view: Matrix4x4,
@@ -282,14 +291,34 @@ impl CameraUniform {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MeshBuffer {
-    pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
+    pub vertex_buffer: Arc<wgpu::Buffer>,
+    pub index_buffer: Arc<wgpu::Buffer>,
     pub indecies_len: u32,
+    pub topology: PrimitiveTopology,
+    /// The shader permutation the mesh is drawn with, depends on the attributes of the primitive.
+    pub shader_defs: ShaderDefs,
+}
+
+/// The camera as seen by the render thread.
+#[derive(Debug, Clone)]
+pub struct ExtractedCamera {
+    pub uniform: CameraUniform,
+    pub buffer: Arc<wgpu::Buffer>,
+    pub bind_group: Arc<wgpu::BindGroup>,
+    pub bind_group_layout: Arc<wgpu::BindGroupLayout>,
+}
+
+/// Render world resource with everything [`GLTFRenderer`] draws, filled by [`GLTFExtractor`].
+#[derive(Debug, Default)]
+pub struct ExtractedScene {
+    pub camera: Option<ExtractedCamera>,
+    pub meshes: Vec<MeshBuffer>,
+    pub render_mode: RenderMode,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum AnimationLoop {
     Enable,
     Disable,
@@ -301,23 +330,196 @@ impl Default for AnimationLoop {
     }
 }
 
+impl AnimationLoop {
+    pub fn toggled(self) -> Self {
+        match self {
+            AnimationLoop::Enable => AnimationLoop::Disable,
+            AnimationLoop::Disable => AnimationLoop::Enable,
+        }
+    }
+}
+
+/// How [`GLTFRenderer`] draws the meshes, a main world resource that can be changed at any time.
+///
+/// Every mode has its own pipelines, they are built the first time the mode is used and cached after.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderMode {
+    #[default]
+    Solid,
+    /// Needs `POLYGON_MODE_LINE`, drawn solid without it.
+    Wireframe,
+    /// The vertices as a point cloud.
+    Points,
+    /// The solid mesh with its edges drawn on top, edges are left out without `POLYGON_MODE_LINE`.
+    WireframeOnSolid,
+}
+
+impl RenderMode {
+    /// The mode after this one, `M` cycles through them in this order.
+    pub fn next(self) -> Self {
+        match self {
+            RenderMode::Solid => RenderMode::Wireframe,
+            RenderMode::Wireframe => RenderMode::Points,
+            RenderMode::Points => RenderMode::WireframeOnSolid,
+            RenderMode::WireframeOnSolid => RenderMode::Solid,
+        }
+    }
+
+    fn draws_lines(self) -> bool {
+        matches!(self, RenderMode::Wireframe | RenderMode::WireframeOnSolid)
+    }
+}
+
+impl FromStr for RenderMode {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().replace('-', "_").as_str() {
+            "solid" => Ok(RenderMode::Solid),
+            "wireframe" => Ok(RenderMode::Wireframe),
+            "points" => Ok(RenderMode::Points),
+            "wireframe_on_solid" => Ok(RenderMode::WireframeOnSolid),
+            _ => Err("solid, wireframe, points or wireframe_on_solid"),
+        }
+    }
+}
+
+impl fmt::Display for RenderMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RenderMode::Solid => "solid",
+            RenderMode::Wireframe => "wireframe",
+            RenderMode::Points => "points",
+            RenderMode::WireframeOnSolid => "wireframe_on_solid",
+        })
+    }
+}
+
+/// Views a single glTF model: loads it on startup, spawns an orbiting camera and draws it.
+///
+/// The camera orbits while the [`AnimationLoop`] resource is enabled, `Space` toggles it.
+/// The meshes are drawn in the mode of the [`RenderMode`] resource, `M` switches to the next one.
+/// The keys are the default [`CameraUpdater::bindings`] of the [`Input`] resource.
+#[derive(Debug)]
+pub struct GltfViewerPlugin {
+    path: String,
+    animation: AnimationLoop,
+    render_mode: RenderMode,
+    depth_mode: DepthMode,
+    bindings: InputMap,
+}
+
+impl GltfViewerPlugin {
+    pub fn new(path: impl Into<String>, animation: AnimationLoop) -> Self {
+        Self {
+            path: path.into(),
+            animation,
+            render_mode: RenderMode::default(),
+            depth_mode: DepthMode::default(),
+            bindings: InputMap::default(),
+        }
+    }
+
+    /// Replaces the default bindings of the actions and axes bound in `bindings`.
+    pub fn with_bindings(mut self, bindings: InputMap) -> Self {
+        self.bindings = bindings;
+        self
+    }
+
+    pub fn with_render_mode(mut self, render_mode: RenderMode) -> Self {
+        self.render_mode = render_mode;
+        self
+    }
+
+    /// Reversed-Z trades the far plane for precision far away from the camera.
+    pub fn with_depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.depth_mode = depth_mode;
+        self
+    }
+}
+
+impl Plugin for GltfViewerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ModelLoaded>()
+            .add_event::<CameraChanged>()
+            .add_event::<AnimationToggled>()
+            .insert_resource(self.animation)
+            .insert_resource(self.render_mode);
+
+        let mut bindings = CameraUpdater::bindings();
+        bindings.merge(self.bindings.clone());
+        app.add_plugin(InputPlugin::new(bindings));
+
+        app.add_system(
+            SystemDescriptor::new(GLTFLoader::new(self.path.clone()))
+                .in_stage(Stage::Update)
+                .label(GLTFLoader::LABEL)
+                .before(CameraUpdater::LABEL),
+        )
+        .add_system(
+            SystemDescriptor::new(CameraUpdater::new(self.animation, self.depth_mode))
+                .in_stage(Stage::Update)
+                .label(CameraUpdater::LABEL),
+        )
+        .add_system(
+            SystemDescriptor::new(GLTFExtractor)
+                .in_stage(Stage::Extract)
+                .label(GLTFExtractor::LABEL),
+        )
+        .add_render_node(GLTFRenderer::LABEL, GLTFRenderer::new(self.depth_mode))
+        .add_render_node(
+            GLTFWireframeRenderer::LABEL,
+            GLTFWireframeRenderer::new(self.depth_mode),
+        );
+    }
+}
+
+/// Sent by [`GLTFLoader`] once the model's meshes were spawned.
+#[derive(Debug, Clone)]
+pub struct ModelLoaded {
+    pub path: String,
+    pub name: Option<String>,
+    pub entities: Vec<Entity>,
+}
+
+/// Sent by [`CameraUpdater`] whenever a camera moved.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraChanged {
+    pub camera: Entity,
+}
+
+/// Sent by [`CameraUpdater`] when the [`AnimationLoop`] resource was switched on or off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationToggled(pub AnimationLoop);
+
+/// Loads the glTF file on a worker thread and spawns its meshes into the world once it's done,
+/// so the window keeps handling input while large models load.
 #[derive(Debug, Default)]
 pub struct GLTFLoader {
     path: String,
     name: Option<String>,
     meshes: Vec<Vec<Primitive>>,
-    state: Option<State>,
-    is_animation_anabled: AnimationLoop,
+    loading: Option<Receiver<LoadedModel>>,
+}
+
+/// Sent from the worker thread of [`GLTFLoader`] to the system.
+#[derive(Debug)]
+struct LoadedModel {
+    name: Option<String>,
+    transform: ModelTransform,
+    buffers: Vec<MeshBuffer>,
 }
 
 impl GLTFLoader {
-    pub fn new(path: String, enable_animation: AnimationLoop) -> GLTFLoader {
+    pub const LABEL: SystemLabel = "gltf_loader";
+
+    pub fn new(path: String) -> GLTFLoader {
         GLTFLoader {
-            state: None,
             path,
             name: None,
             meshes: vec![],
-            is_animation_anabled: enable_animation,
+            loading: None,
         }
     }
 
@@ -348,33 +550,32 @@ impl GLTFLoader {
                             .map(|v| AttributeValues::Float32x3(v.collect()))
                             .expect("Positions From Reader");
 
+                        // Points and lines usually come without normals
                         let normals = reader
                             .read_normals()
                             .map(|v| AttributeValues::Float32x3(v.collect()))
-                            .expect("Normals From Reader");
+                            .unwrap_or_else(|| {
+                                AttributeValues::from(vec![[0.0, 0.0, 1.0]; positions.len()])
+                            });
 
                         let tex_coords = reader
                             .read_tex_coords(0)
-                            .map(|v| AttributeValues::Float32x2(v.into_f32().collect()))
-                            .or_else(|| {
-                                let len = positions.len();
-                                let uvs = vec![[0.0, 0.0]; len];
-                                Some(AttributeValues::from(uvs))
-                            })
-                            .expect("TexCoords From Reader");
+                            .map(|v| AttributeValues::Float32x2(v.into_f32().collect()));
 
-                        let indecies = reader
-                            .read_indices()
-                            .expect("Indecies From Reader")
-                            .into_u32()
-                            .collect::<Vec<u32>>();
+                        // Non-indexed primitives are drawn with their vertices in order
+                        let indecies = match reader.read_indices() {
+                            Some(indecies) => indecies.into_u32().collect::<Vec<u32>>(),
+                            None => (0..positions.len() as u32).collect(),
+                        };
 
                         println!("Vertices: {}", positions.len() + indecies.len());
 
                         mesh.insert(Primitive::POSITIONS, positions)
                             .insert(Primitive::NORMALS, normals)
-                            .insert(Primitive::TEX_COORDS, tex_coords)
                             .set_indecies(indecies);
+                        if let Some(tex_coords) = tex_coords {
+                            mesh.insert(Primitive::TEX_COORDS, tex_coords);
+                        }
 
                         mesh
                     })
@@ -387,79 +588,6 @@ impl GLTFLoader {
         self.meshes = meshes;
         self.name = Some(label);
     }
-
-    fn draw(&self, state: &State, context: &Context) -> Result<(), wgpu::SurfaceError> {
-        let output = context.surface.get_current_texture()?;
-
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-
-        let depth_texture = context.device.create_texture(&TextureDescriptor {
-            label: Default::default(),
-            size: Extent3d {
-                width: state.size.width,
-                height: state.size.height,
-                ..Default::default()
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth24Plus,
-            usage: TextureUsages::RENDER_ATTACHMENT,
-        });
-
-        let depth_view = depth_texture.create_view(&TextureViewDescriptor::default());
-        let mut encoder = context
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.3,
-                            g: 0.5,
-                            b: 0.7,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                }],
-                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                    view: &depth_view,
-                    depth_ops: Some(Operations {
-                        load: LoadOp::Clear(1.0),
-                        store: true,
-                    }),
-                    stencil_ops: Some(Operations {
-                        load: LoadOp::Load,
-                        store: true,
-                    }),
-                }),
-            });
-
-            render_pass.set_pipeline(state.render_pipeline.as_ref().unwrap());
-            render_pass.set_bind_group(0, &state.camera_state.camera_bind_group, &[]);
-            state
-                .meshes
-                .as_ref()
-                .unwrap()
-                .iter()
-                .for_each(|mesh| render_pass.draw_mesh(mesh));
-        }
-
-        context.queue.submit(std::iter::once(encoder.finish()));
-        // context.queue.on_submitted_work_done() // Fence Equivalent
-        output.present();
-        Ok(())
-    }
 }
 
 impl GLTFLoader {
@@ -470,7 +598,8 @@ impl GLTFLoader {
                 mesh.iter()
                     .map(|primitive| {
                         let default_vec_3 = vec![];
-                        let default_vec_2 = vec![];
+                        let mut default_vec_2 = vec![];
+                        let mut shader_defs = ShaderDefs::new();
 
                         let positions = if let AttributeValues::Float32x3(positions) =
                             primitive.get(&Primitive::POSITIONS).unwrap()
@@ -488,11 +617,13 @@ impl GLTFLoader {
                             &default_vec_3
                         };
 
-                        let tex_coords = if let AttributeValues::Float32x2(tex_coords) =
-                            primitive.get(&Primitive::TEX_COORDS).unwrap()
+                        let tex_coords = if let Some(AttributeValues::Float32x2(tex_coords)) =
+                            primitive.get(&Primitive::TEX_COORDS)
                         {
+                            shader_defs = shader_defs.with("HAS_TEX_COORDS");
                             tex_coords
                         } else {
+                            default_vec_2.resize(positions.len(), [0.0, 0.0]);
                             &default_vec_2
                         };
 
@@ -519,9 +650,11 @@ impl GLTFLoader {
                             });
 
                         MeshBuffer {
-                            vertex_buffer,
-                            index_buffer,
+                            vertex_buffer: Arc::new(vertex_buffer),
+                            index_buffer: Arc::new(index_buffer),
                             indecies_len: primitive.indecies().len() as u32,
+                            topology: primitive.topology,
+                            shader_defs,
                         }
                     })
                     .collect::<Vec<_>>()
@@ -529,135 +662,725 @@ impl GLTFLoader {
             .flatten()
             .collect::<Vec<_>>()
     }
+}
+
+impl System for GLTFLoader {
+    fn init(&mut self, context: &Context, _world: &mut World) {
+        let mut loader = GLTFLoader::new(self.path.clone());
+        let device = context.device.clone();
+        let (sender, receiver) = mpsc::channel();
+
+        let spawned = thread::Builder::new()
+            .name("gltf_loader".to_owned())
+            .spawn(move || {
+                loader.load();
+
+                let primitive = &loader.meshes[0][0];
+                let aabb = primitive.aabb.as_ref().unwrap();
+
+                // The system is gone if the app exited while loading
+                let _ = sender.send(LoadedModel {
+                    transform: ModelTransform(get_transform(aabb)),
+                    buffers: loader.create_mesh_buffers(&device),
+                    name: loader.name,
+                });
+            });
+
+        match spawned {
+            Ok(_) => self.loading = Some(receiver),
+            Err(error) => log::error!("Failed to start loading `{}`, {error}", self.path),
+        }
+    }
+
+    fn run(&mut self, context: &Context, world: &mut World) {
+        let receiver = match self.loading {
+            Some(ref receiver) => receiver,
+            None => return,
+        };
+
+        // A headless app renders a single frame, which has to show the model
+        let loaded = match context.is_headless() {
+            true => receiver.recv().map_err(|_| TryRecvError::Disconnected),
+            false => receiver.try_recv(),
+        };
+
+        let model = match loaded {
+            Ok(model) => model,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                log::error!("Failed to load `{}`", self.path);
+                self.loading = None;
+                return;
+            }
+        };
+        self.loading = None;
+        self.name = model.name;
+
+        let entities = model
+            .buffers
+            .into_iter()
+            .map(|buffer| world.spawn().insert(buffer).insert(model.transform).id())
+            .collect();
+
+        world.send_event(ModelLoaded {
+            path: self.path.clone(),
+            name: self.name.clone(),
+            entities,
+        });
+    }
+}
+
+/// What the [`Input`] asks [`CameraUpdater`] to do in a frame.
+#[derive(Debug, Default)]
+struct CameraInput {
+    toggle_animation: bool,
+    next_render_mode: bool,
+    /// Whether a drag started or ended, the cursor is hidden while dragging.
+    grab_cursor: Option<bool>,
+    /// Steps of the camera speed, see [`CameraController::move_by`].
+    forward: f32,
+    orbit: f32,
+    zoom: f32,
+}
+
+impl CameraInput {
+    fn read(input: &Input, delta: f32) -> Self {
+        let steps = CameraUpdater::MOVEMENT_RATE * delta;
+        let dragged = match input.pressed(CameraUpdater::DRAG) {
+            true => input.axis(CameraUpdater::DRAG_ORBIT),
+            false => 0.0,
+        };
+
+        Self {
+            toggle_animation: input.just_pressed(CameraUpdater::TOGGLE_ANIMATION),
+            next_render_mode: input.just_pressed(CameraUpdater::CYCLE_RENDER_MODE),
+            grab_cursor: match (
+                input.just_pressed(CameraUpdater::DRAG),
+                input.just_released(CameraUpdater::DRAG),
+            ) {
+                (true, false) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            },
+            forward: input.axis(CameraUpdater::MOVE_FORWARD) * steps,
+            // Dragging to the right turns the model to the right
+            orbit: input.axis(CameraUpdater::ORBIT) * steps - dragged * CameraUpdater::DRAG_RATE,
+            zoom: input.axis(CameraUpdater::ZOOM),
+        }
+    }
+
+    fn moves_camera(&self) -> bool {
+        self.forward != 0.0 || self.orbit != 0.0 || self.zoom != 0.0
+    }
+}
+
+/// Spawns the camera and moves it on input or while the animation loop is enabled.
+#[derive(Debug, Default)]
+pub struct CameraUpdater {
+    /// The value of the `AnimationLoop` resource in the last frame.
+    animation: AnimationLoop,
+    depth_mode: DepthMode,
+    is_dirty: bool,
+}
+
+impl CameraUpdater {
+    pub const LABEL: SystemLabel = "camera_updater";
+    pub const MOVE_FORWARD: &'static str = "move_forward";
+    pub const ORBIT: &'static str = "orbit";
+    pub const ZOOM: &'static str = "zoom";
+    /// Orbits with the [`CameraUpdater::DRAG_ORBIT`] axis while held.
+    pub const DRAG: &'static str = "drag";
+    pub const DRAG_ORBIT: &'static str = "drag_orbit";
+    pub const TOGGLE_ANIMATION: &'static str = "toggle_animation";
+    pub const CYCLE_RENDER_MODE: &'static str = "cycle_render_mode";
+    /// Movement steps per second while a key is held, about the key repeat rate the camera used to move at.
+    const MOVEMENT_RATE: f32 = 30.0;
+    /// Orbit steps per pixel of a drag.
+    const DRAG_RATE: f32 = 0.05;
+
+    /// `W`/`S` and the arrow keys move, `A`/`D` orbit, the wheel zooms and dragging with the left button orbits.
+    pub fn bindings() -> InputMap {
+        use VirtualKeyCode::*;
+
+        let keys = |negative, positive| AxisBinding::Buttons {
+            negative: Button::Key(negative),
+            positive: Button::Key(positive),
+        };
+
+        InputMap::default()
+            .with_axis(CameraUpdater::MOVE_FORWARD, [keys(S, W), keys(Down, Up)])
+            .with_axis(CameraUpdater::ORBIT, [keys(A, D), keys(Left, Right)])
+            .with_axis(CameraUpdater::ZOOM, [AxisBinding::MouseWheel])
+            .with_axis(CameraUpdater::DRAG_ORBIT, [AxisBinding::MouseMotionX])
+            .with_action(CameraUpdater::DRAG, [Button::Mouse(MouseButton::Left)])
+            .with_action(CameraUpdater::TOGGLE_ANIMATION, [Button::Key(Space)])
+            .with_action(CameraUpdater::CYCLE_RENDER_MODE, [Button::Key(M)])
+    }
+
+    pub fn new(enable_animation: AnimationLoop, depth_mode: DepthMode) -> Self {
+        Self {
+            animation: enable_animation,
+            depth_mode,
+            is_dirty: true,
+        }
+    }
+}
+
+impl System for CameraUpdater {
+    fn init(&mut self, context: &Context, world: &mut World) {
+        world.spawn().insert(CameraState::new(
+            &context.device,
+            &context.config,
+            self.depth_mode,
+        ));
+    }
+
+    fn process_events<'a>(&mut self, event: &'a Event<()>, context: &Context, world: &mut World) {
+        match event {
+            // Minimizing resizes to zero, the last aspect ratio is kept until it's restored
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                window_id,
+            } if Some(*window_id) == context.window_id() && size.width > 0 && size.height > 0 => {
+                world
+                    .query::<&mut CameraState>()
+                    .for_each(|mut camera| camera.resize(*size));
+                self.is_dirty = true;
+            }
+            _ => {}
+        };
+    }
+
+    fn run(&mut self, context: &Context, world: &mut World) {
+        let delta = world
+            .get_resource::<Time>()
+            .map_or(0.0, Time::delta_seconds);
+        let input = world
+            .get_resource::<Input>()
+            .map(|input| CameraInput::read(input, delta))
+            .unwrap_or_default();
+
+        if input.toggle_animation {
+            if let Some(animation) = world.get_resource_mut::<AnimationLoop>() {
+                *animation = animation.toggled();
+            }
+        }
+        if input.next_render_mode {
+            if let Some(render_mode) = world.get_resource_mut::<RenderMode>() {
+                *render_mode = render_mode.next();
+            }
+        }
+        if let (Some(grab), Some(window)) = (input.grab_cursor, context.window()) {
+            window.set_cursor_visible(!grab);
+            if let Err(error) = window.set_cursor_grab(grab) {
+                log::debug!("Failed to grab the cursor: {error}");
+            }
+        }
+        if input.moves_camera() {
+            self.is_dirty = true;
+        }
+
+        let animation = world
+            .get_resource::<AnimationLoop>()
+            .copied()
+            .unwrap_or_default();
+        if animation != self.animation {
+            self.animation = animation;
+            world.send_event(AnimationToggled(animation));
+        }
+
+        let is_animating = animation == AnimationLoop::Enable;
+        if !is_animating && !self.is_dirty {
+            return;
+        }
+
+        let transform = match world.query::<&ModelTransform>().iter().next() {
+            Some(transform) => transform.0,
+            None => return,
+        };
+
+        let mut changed = vec![];
+        world
+            .query::<(Entity, &mut CameraState)>()
+            .for_each(|(entity, mut camera)| {
+                if is_animating {
+                    camera.animate(delta);
+                }
+                camera.apply_input(&input);
+                camera.update(&transform);
+                changed.push(entity);
+            });
+
+        changed.into_iter().for_each(|camera| {
+            world.send_event(CameraChanged { camera });
+        });
+
+        self.is_dirty = false;
+    }
+}
+
+/// Copies the meshes, the first camera and the [`RenderMode`] from the main world into the [`ExtractedScene`].
+#[derive(Debug, Default)]
+pub struct GLTFExtractor;
+
+impl GLTFExtractor {
+    pub const LABEL: SystemLabel = "gltf_extractor";
+}
+
+impl System for GLTFExtractor {
+    fn run(&mut self, _context: &Context, world: &mut World) {
+        let main_world = match world.get_resource::<MainWorld>() {
+            Some(main_world) => main_world,
+            None => return,
+        };
+
+        let scene = ExtractedScene {
+            camera: main_world
+                .query::<&CameraState>()
+                .iter()
+                .next()
+                .map(|camera| camera.extract()),
+            meshes: main_world.query::<&MeshBuffer>().iter().cloned().collect(),
+            render_mode: main_world
+                .get_resource::<RenderMode>()
+                .copied()
+                .unwrap_or_default(),
+        };
+
+        world.insert_resource(scene);
+    }
+}
+
+/// Draws the [`ExtractedScene`] of the render world in the [`RenderMode`] it was extracted with.
+#[derive(Debug, Default)]
+pub struct GLTFRenderer {
+    /// One pipeline per shader permutation and primitive state of the meshes.
+    pipelines: PipelineCache,
+    /// The mode of the last frame, to log when it changes.
+    render_mode: Option<RenderMode>,
+    depth_mode: DepthMode,
+}
+
+impl GLTFRenderer {
+    pub const LABEL: NodeLabel = "gltf_renderer";
+    /// The depth buffer of the meshes, [`GLTFWireframeRenderer`] tests its edges against it.
+    pub const DEPTH: ResourceLabel = "gltf_depth";
+    /// The camera uniform, uploaded while the renderer prepares the frame.
+    pub const CAMERA: ResourceLabel = "gltf_camera";
+    pub const VERTEX_SHADER: &'static str = "gltf.vert.wgsl";
+    pub const FRAGMENT_SHADER: &'static str = "gltf.frag.wgsl";
+    const CLEAR_COLOR: wgpu::Color = wgpu::Color {
+        r: 0.3,
+        g: 0.5,
+        b: 0.7,
+        a: 1.0,
+    };
+
+    pub fn new(depth_mode: DepthMode) -> Self {
+        Self {
+            depth_mode,
+            ..Default::default()
+        }
+    }
+
+    /// The shaders and their includes, embedded for when [`Shaders`] doesn't read them from disk.
+    const SHADER_SOURCES: [(&'static str, &'static str); 4] = [
+        (
+            Self::VERTEX_SHADER,
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/assets/shaders/gltf.vert.wgsl"
+            )),
+        ),
+        (
+            Self::FRAGMENT_SHADER,
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/assets/shaders/gltf.frag.wgsl"
+            )),
+        ),
+        (
+            "include/camera.wgsl",
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/assets/shaders/include/camera.wgsl"
+            )),
+        ),
+        (
+            "include/gltf_vertex.wgsl",
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/assets/shaders/include/gltf_vertex.wgsl"
+            )),
+        ),
+    ];
 
     pub fn create_render_pipeline(
-        &self,
         context: &Context,
         layout: &wgpu::PipelineLayout,
+        vertex_shader: &wgpu::ShaderModule,
+        frag_shader: &wgpu::ShaderModule,
+        key: &PipelineKey,
     ) -> wgpu::RenderPipeline {
-        let (vertex_shader, frag_shader) = create_shaders(&context.device);
-
         context
             .device
             .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Render Pipeline"),
+                label: Some(&format!("Render Pipeline {:?}", key.topology)),
                 layout: Some(layout),
                 vertex: wgpu::VertexState {
-                    module: &vertex_shader,
+                    module: vertex_shader,
                     entry_point: "main",
-                    buffers: &[ModelVertex::desc()],
+                    buffers: std::slice::from_ref(&key.vertex_layout),
                 },
                 fragment: Some(wgpu::FragmentState {
-                    module: &frag_shader,
+                    module: frag_shader,
                     entry_point: "main",
                     targets: &[wgpu::ColorTargetState {
                         format: context.config.format,
-                        blend: Some(wgpu::BlendState {
-                            alpha: BlendComponent {
-                                operation: wgpu::BlendOperation::Add,
-                                src_factor: wgpu::BlendFactor::Zero,
-                                dst_factor: wgpu::BlendFactor::One,
-                            },
-                            color: BlendComponent::default(),
-                        }),
+                        blend: key.blend,
                         write_mask: wgpu::ColorWrites::ALL,
                     }],
                 }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    polygon_mode: PolygonMode::Line,
-                    unclipped_depth: false,
-                    conservative: false,
+                primitive: key.primitive_state(),
+                depth_stencil: key.depth_stencil_state(),
+                multisample: wgpu::MultisampleState {
+                    count: context.sample_count,
+                    ..Default::default()
                 },
-                depth_stencil: Some(DepthStencilState {
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    format: wgpu::TextureFormat::Depth24Plus,
-                    stencil: StencilState::default(),
-                    bias: DepthBiasState::default(),
-                }),
-                multisample: wgpu::MultisampleState::default(),
                 multiview: None,
             })
     }
-}
 
-impl System for GLTFLoader {
-    fn init(&mut self, context: &Context) {
-        let Context { device, .. } = context;
-        self.load();
-
-        let buffers = self.create_mesh_buffers(&*device);
-        let primitive = &self.meshes[0][0];
-        let aabb = primitive.aabb.as_ref().unwrap();
-
-        let mut state = State::new(&context.device, &context.config);
-        state.update(context, &get_transform(aabb));
-
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&state.camera_state.camera_bind_group_layout],
-                push_constant_ranges: &[],
-            });
+    /// The state every mesh is drawn with, before the render mode is applied.
+    fn base_key(topology: PrimitiveTopology, depth_mode: DepthMode) -> PipelineKey {
+        PipelineKey::new(topology, ModelVertex::desc())
+            .with_blend(Some(wgpu::BlendState {
+                alpha: BlendComponent {
+                    operation: wgpu::BlendOperation::Add,
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                },
+                color: BlendComponent::default(),
+            }))
+            .with_depth(Some(depth_mode.depth_state(DepthState::default())))
+    }
 
-        state.render_pipeline = Some(self.create_render_pipeline(context, &render_pipeline_layout));
-        state.meshes = Some(buffers);
-        self.state = Some(state);
+    /// Whether the faces of `mesh` can be drawn as lines, which needs an optional feature.
+    /// Points and lines have no edges, they are drawn as they are.
+    fn has_edges(mesh: &MeshBuffer, context: &Context) -> bool {
+        matches!(
+            mesh.topology,
+            PrimitiveTopology::TriangleList | PrimitiveTopology::TriangleStrip
+        ) && context
+            .device
+            .features()
+            .contains(Features::POLYGON_MODE_LINE)
     }
 
-    fn process_events<'a>(&mut self, event: &'a Event<()>, context: &Context) {
-        match event {
-            Event::WindowEvent {
-                ref event,
-                window_id,
-            } if *window_id == context.window().id() => {
-                if self.state.as_mut().unwrap().input(event, context) {
-                    let primitive = &self.meshes[0][0];
-                    let aabb = primitive.aabb.as_ref().unwrap();
-                    self.state
-                        .as_mut()
-                        .unwrap()
-                        .update(context, &get_transform(aabb));
-                };
+    /// The shader permutation and pipeline state `mesh` is drawn with in `mode`.
+    /// The edges of [`RenderMode::WireframeOnSolid`] are drawn by [`GLTFWireframeRenderer`].
+    fn pipeline_key(
+        &self,
+        mesh: &MeshBuffer,
+        mode: RenderMode,
+        context: &Context,
+    ) -> (ShaderDefs, PipelineKey) {
+        let key = match mode {
+            // The index buffer drawn as points, every vertex is drawn at least once
+            RenderMode::Points => Self::base_key(PrimitiveTopology::PointList, self.depth_mode),
+            // Falls back to solid without the feature
+            RenderMode::Wireframe if Self::has_edges(mesh, context) => {
+                Self::base_key(mesh.topology, self.depth_mode).with_polygon_mode(PolygonMode::Line)
+            }
+            _ => Self::base_key(mesh.topology, self.depth_mode),
+        };
 
-                if let WindowEvent::KeyboardInput {
-                    input:
-                        KeyboardInput {
-                            virtual_keycode: Some(keycode),
-                            ..
-                        },
-                    ..
-                } = event
-                {
-                    match keycode {
-                        VirtualKeyCode::Space => {
-                            self.is_animation_anabled = AnimationLoop::Enable;
-                        }
-                        _ => {}
+        (mesh.shader_defs.clone(), key)
+    }
+
+    /// Adds the embedded shaders to the [`Shaders`] of the render world.
+    fn embed_shaders(world: &mut World) {
+        if world.get_resource::<Shaders>().is_none() {
+            world.insert_resource(Shaders::default());
+        }
+
+        let shaders = world.get_resource_mut::<Shaders>().unwrap();
+        for (name, source) in Self::SHADER_SOURCES {
+            shaders.embed(name, source);
+        }
+    }
+
+    /// Compiles the shader permutation of `defs`.
+    fn load_shaders(
+        context: &Context,
+        world: &mut World,
+        defs: &ShaderDefs,
+    ) -> Result<[(Arc<wgpu::ShaderModule>, u64); 2], ShaderError> {
+        let shaders = world
+            .get_resource_mut::<Shaders>()
+            .expect("the shaders are inserted in `init`");
+
+        let vertex = shaders.load(&context.device, Self::VERTEX_SHADER, defs)?;
+        let vertex = (vertex.module.clone(), vertex.generation);
+
+        let fragment = shaders.load(&context.device, Self::FRAGMENT_SHADER, defs)?;
+        let fragment = (fragment.module.clone(), fragment.generation);
+
+        Ok([vertex, fragment])
+    }
+
+    /// Builds the pipelines of `keys` that aren't cached or whose shaders were reloaded.
+    fn prepare_pipelines(
+        pipelines: &mut PipelineCache,
+        keys: HashSet<(ShaderDefs, PipelineKey)>,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        context: &Context,
+        world: &mut World,
+    ) {
+        for (defs, key) in keys {
+            let [(vertex_shader, vertex_generation), (frag_shader, frag_generation)] =
+                match GLTFRenderer::load_shaders(context, world, &defs) {
+                    Ok(shaders) => shaders,
+                    // Keeps the cached pipeline of the permutation, if it was built before
+                    Err(error) => {
+                        log::error!("{error}\nKeeping the last pipeline of {defs}");
+                        continue;
                     }
-                }
+                };
+
+            pipelines.prepare(
+                &context.device,
+                &defs,
+                &key,
+                &[vertex_generation, frag_generation],
+                |key| {
+                    let render_pipeline_layout =
+                        context
+                            .device
+                            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                                label: Some("Render Pipeline Layout"),
+                                bind_group_layouts: &[bind_group_layout],
+                                push_constant_ranges: &[],
+                            });
+
+                    GLTFRenderer::create_render_pipeline(
+                        context,
+                        &render_pipeline_layout,
+                        &vertex_shader,
+                        &frag_shader,
+                        key,
+                    )
+                },
+            );
+        }
+    }
+
+    /// Draws every mesh with the pipeline of its key, meshes whose pipeline failed to build are skipped.
+    fn draw_meshes<'a>(
+        pipelines: &'a PipelineCache,
+        pass: &mut wgpu::RenderPass<'a>,
+        camera: &'a ExtractedCamera,
+        draws: impl Iterator<Item = ((ShaderDefs, PipelineKey), &'a MeshBuffer)>,
+    ) {
+        pass.set_bind_group(0, &camera.bind_group, &[]);
+
+        let mut current_pipeline = None;
+        for ((defs, key), mesh) in draws {
+            let pipeline = match pipelines.get(&defs, &key) {
+                Some(pipeline) => pipeline,
+                None => continue,
+            };
+
+            if !current_pipeline.is_some_and(|current| std::ptr::eq(current, pipeline)) {
+                pass.set_pipeline(pipeline);
+                current_pipeline = Some(pipeline);
             }
-            Event::RedrawRequested(window_id) if *window_id == context.window().id() => {
-                if let AnimationLoop::Enable = self.is_animation_anabled {
-                    self.state.as_mut().unwrap().animate();
-                    let primitive = &self.meshes[0][0];
-                    let aabb = primitive.aabb.as_ref().unwrap();
-                    self.state
-                        .as_mut()
-                        .unwrap()
-                        .update(context, &get_transform(aabb));
-                }
-                let _ = self.draw(self.state.as_ref().unwrap(), context);
+            pass.draw_mesh(mesh);
+        }
+    }
+}
+
+impl Node for GLTFRenderer {
+    fn declare(&self, node: &mut NodeBuilder) {
+        node.create_texture(Self::DEPTH, TextureInfo::frame(self.depth_mode.format()))
+            .color_attachment(RenderGraph::SURFACE, Self::CLEAR_COLOR)
+            .depth_attachment(Self::DEPTH, self.depth_mode.clear_value())
+            .write(Self::CAMERA);
+    }
+
+    fn init(&mut self, _context: &Context, world: &mut World) {
+        GLTFRenderer::embed_shaders(world);
+    }
+
+    fn prepare(&mut self, context: &Context, world: &mut World) {
+        let (bind_group_layout, render_mode, keys) = match world.get_resource::<ExtractedScene>() {
+            Some(ExtractedScene {
+                camera: Some(camera),
+                meshes,
+                render_mode,
+            }) => {
+                context.queue.write_buffer(
+                    &camera.buffer,
+                    0,
+                    bytemuck::cast_slice(&[camera.uniform]),
+                );
+
+                (
+                    camera.bind_group_layout.clone(),
+                    *render_mode,
+                    meshes
+                        .iter()
+                        .map(|mesh| self.pipeline_key(mesh, *render_mode, context))
+                        .collect::<HashSet<_>>(),
+                )
             }
-            Event::MainEventsCleared => {
-                context.window().request_redraw();
+            _ => return,
+        };
+
+        if self.render_mode != Some(render_mode) {
+            self.render_mode = Some(render_mode);
+
+            let has_lines = context
+                .device
+                .features()
+                .contains(Features::POLYGON_MODE_LINE);
+            match render_mode.draws_lines() && !has_lines {
+                true => log::warn!(
+                    "The adapter doesn't support `POLYGON_MODE_LINE`, drawing {render_mode} without edges"
+                ),
+                false => log::info!("Drawing in {render_mode} mode"),
             }
-            _ => {}
+        }
+
+        GLTFRenderer::prepare_pipelines(
+            &mut self.pipelines,
+            keys,
+            &bind_group_layout,
+            context,
+            world,
+        );
+    }
+
+    fn run<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, node: &NodeContext<'a>) {
+        let (camera, meshes, render_mode) = match node.world.get_resource::<ExtractedScene>() {
+            Some(ExtractedScene {
+                camera: Some(camera),
+                meshes,
+                render_mode,
+            }) => (camera, meshes, *render_mode),
+            _ => return,
         };
+
+        let draws = meshes
+            .iter()
+            .map(|mesh| (self.pipeline_key(mesh, render_mode, node.context), mesh));
+        GLTFRenderer::draw_meshes(&self.pipelines, pass, camera, draws);
+    }
+}
+
+/// Draws the edges of [`RenderMode::WireframeOnSolid`] over the meshes drawn by [`GLTFRenderer`].
+#[derive(Debug, Default)]
+pub struct GLTFWireframeRenderer {
+    pipelines: PipelineCache,
+    depth_mode: DepthMode,
+}
+
+impl GLTFWireframeRenderer {
+    pub const LABEL: NodeLabel = "gltf_wireframe";
+    /// Keeps the edges from fighting with the faces below them.
+    const DEPTH_BIAS: i32 = -4;
+
+    pub fn new(depth_mode: DepthMode) -> Self {
+        Self {
+            depth_mode,
+            ..Default::default()
+        }
+    }
+
+    /// The edges of `mesh`, `None` if it has none to draw.
+    fn pipeline_key(
+        &self,
+        mesh: &MeshBuffer,
+        context: &Context,
+    ) -> Option<(ShaderDefs, PipelineKey)> {
+        let depth = DepthState {
+            write_enabled: false,
+            compare: wgpu::CompareFunction::LessEqual,
+            bias: Self::DEPTH_BIAS,
+            ..Default::default()
+        };
+
+        GLTFRenderer::has_edges(mesh, context).then(|| {
+            (
+                mesh.shader_defs.clone().with("WIREFRAME"),
+                GLTFRenderer::base_key(mesh.topology, self.depth_mode)
+                    .with_polygon_mode(PolygonMode::Line)
+                    .with_depth(Some(self.depth_mode.depth_state(depth))),
+            )
+        })
+    }
+}
+
+impl Node for GLTFWireframeRenderer {
+    fn declare(&self, node: &mut NodeBuilder) {
+        node.color_attachment(RenderGraph::SURFACE, GLTFRenderer::CLEAR_COLOR)
+            .depth_test(GLTFRenderer::DEPTH)
+            .read(GLTFRenderer::CAMERA);
+    }
+
+    fn init(&mut self, _context: &Context, world: &mut World) {
+        GLTFRenderer::embed_shaders(world);
+    }
+
+    fn is_active(&self, world: &World) -> bool {
+        world
+            .get_resource::<ExtractedScene>()
+            .is_some_and(|scene| scene.render_mode == RenderMode::WireframeOnSolid)
+    }
+
+    fn prepare(&mut self, context: &Context, world: &mut World) {
+        if !self.is_active(world) {
+            return;
+        }
+
+        let (bind_group_layout, keys) = match world.get_resource::<ExtractedScene>() {
+            Some(ExtractedScene {
+                camera: Some(camera),
+                meshes,
+                ..
+            }) => (
+                camera.bind_group_layout.clone(),
+                meshes
+                    .iter()
+                    .filter_map(|mesh| self.pipeline_key(mesh, context))
+                    .collect::<HashSet<_>>(),
+            ),
+            _ => return,
+        };
+
+        GLTFRenderer::prepare_pipelines(
+            &mut self.pipelines,
+            keys,
+            &bind_group_layout,
+            context,
+            world,
+        );
+    }
+
+    fn run<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, node: &NodeContext<'a>) {
+        let (camera, meshes) = match node.world.get_resource::<ExtractedScene>() {
+            Some(ExtractedScene {
+                camera: Some(camera),
+                meshes,
+                ..
+            }) => (camera, meshes),
+            _ => return,
+        };
+
+        let draws = meshes
+            .iter()
+            .filter_map(|mesh| self.pipeline_key(mesh, node.context).map(|key| (key, mesh)));
+        GLTFRenderer::draw_meshes(&self.pipelines, pass, camera, draws);
     }
 }
 
@@ -737,36 +1460,3 @@ impl WgpuConverter for Mode {
         }
     }
 }
-
-fn create_shaders(device: &wgpu::Device) -> (wgpu::ShaderModule, wgpu::ShaderModule) {
-    // vertex_shader = glCreateShader(GL_VERTEX_SHADER);
-    // glCompileShader(vertex_shader);
-    let vertex_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-        label: Some("GLTF Vertex Shader"),
-        // glShaderSource
-        source: wgpu::ShaderSource::Wgsl(
-            include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/shaders/gltf.vert.wgsl"
-            ))
-            .into(),
-        ),
-    });
-
-    // frag_shader = glCreateShader(GL_FRAGMENT_SHADER);
-    // glCompileShader(frag_shader);
-    let frag_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-        label: Some("GLTF Fragment Shader"),
-        // glShaderSource
-        source: wgpu::ShaderSource::Wgsl(
-            include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/shaders/gltf.frag.wgsl"
-            ))
-            .into(),
-        ),
-    });
-    // device.create_shader_module(&include_wgsl!("shader.wgsl"));
-
-    (vertex_shader, frag_shader)
-}
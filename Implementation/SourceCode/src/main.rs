@@ -3,49 +3,122 @@
 pub mod app;
 pub mod assets;
 pub mod camera;
+pub mod config;
 pub mod controls;
+pub mod ecs;
+pub mod input;
 pub mod loader;
 pub mod mesh;
 pub mod model;
+pub mod offscreen;
+pub mod pipeline;
+pub mod plugin;
+pub mod profiler;
+pub mod recorder;
+pub mod render;
+pub mod render_graph;
+pub mod schedule;
+pub mod screenshot;
+pub mod shaders;
+pub mod time;
 pub mod triangle;
 pub mod triangle_simple;
 pub mod vertex;
+pub mod window;
 
-use app::{App, AppOptions};
-use loader::GLTFLoader;
+#[cfg(test)]
+mod golden;
+
+use app::App;
+use config::Config;
+use loader::{AnimationLoop, GltfViewerPlugin};
+use pipeline::DepthMode;
+use profiler::ProfilerPlugin;
+use recorder::GifRecorderPlugin;
+use screenshot::ScreenshotPlugin;
+use shaders::ShaderHotReloadPlugin;
+use winit::dpi::PhysicalSize;
 
 pub const WINDOW_TITLE: &str = "WebGPU Game";
 pub const ASSETS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets");
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::init();
-
-    let (title, path) = (
-        "Armadillo",
-        format!("{ASSETS_PATH}/models/armadillo/Armadillo.glb"),
-    );
-    // let (title, path) = (
-    //     "Dragon XYZRGB",
-    //     format!("{ASSETS_PATH}/models/dragon-xyzrgb/Dragon_xyzrgb.glb"),
-    // );
-    let (title, path) = ("Dragon", format!("{ASSETS_PATH}/models/dragon/Dragon.glb"));
-    // let (title, path) = ("Cube", format!("{ASSETS_PATH}/models/cube/Cube.glb"));
-    // let (title, path) = (
-    //     "Charizard",
-    //     format!("{ASSETS_PATH}/models/charizard/Charizard.gltf"),
-    // );
-
-    let loader = GLTFLoader::new(path, loader::AnimationLoop::Disable);
+    if std::env::args().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", Config::USAGE);
+        return Ok(());
+    }
+
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{error}\n\n{}", Config::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    env_logger::Builder::new()
+        .filter_level(config.log_level)
+        .parse_default_env()
+        .init();
+
+    if config.validate_shaders {
+        let report = shaders::validation::validate_directory(&config.shaders_path())?;
+        report
+            .diagnostics
+            .iter()
+            .for_each(|diagnostic| eprintln!("{diagnostic}\n"));
+        println!(
+            "Validated {} shader modules, {} failed, skipped {} shaders naga can't parse",
+            report.modules,
+            report.diagnostics.len(),
+            report.skipped
+        );
+
+        std::process::exit(if report.is_ok() { 0 } else { 1 });
+    }
 
     // let mut controls = controls::orbital::OrbitControls::default();
     // controls.object.update();
 
-    App::init(AppOptions {
-        title: Some(title),
+    let options = config.app_options();
+    let path = config.model_path().to_string_lossy().into_owned();
+
+    let mut app = match config.headless {
+        Some(_) => App::headless(
+            options,
+            config.size().unwrap_or(PhysicalSize::new(1280, 720)),
+        ),
+        None => App::init(options)?,
+    };
+    app.add_plugin(
+        GltfViewerPlugin::new(path, AnimationLoop::Disable)
+            .with_render_mode(config.renderer.render_mode)
+            .with_bindings(config.input.clone())
+            .with_depth_mode(match config.renderer.reversed_z {
+                true => DepthMode::ReversedZ,
+                false => DepthMode::Standard,
+            }),
+    )
+    .add_plugin(ScreenshotPlugin::default())
+    .add_plugin(GifRecorderPlugin {
+        settings: config.gif.clone(),
+        record_on_load: config.record_gif.clone(),
         ..Default::default()
-    })
-    .add_system(Box::new(loader))
-    .run();
+    });
+    if config.renderer.profile {
+        app.add_plugin(ProfilerPlugin::default());
+    }
+    if cfg!(debug_assertions) && config.headless.is_none() {
+        app.add_plugin(ShaderHotReloadPlugin {
+            directory: config.shaders_path(),
+            ..Default::default()
+        });
+    }
+
+    match config.headless {
+        Some(output) => app.render_headless()?.save(output)?,
+        None => app.run()?,
+    }
 
     // triangle::run();
     // triangle_simple::run();
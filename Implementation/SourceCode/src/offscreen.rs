@@ -0,0 +1,108 @@
+use std::num::NonZeroU32;
+
+use image::RgbaImage;
+use winit::dpi::PhysicalSize;
+
+/// Color target used when the app runs without a window.
+///
+/// The texture can be rendered into like a swapchain image and copied back
+/// to the CPU with [`OffscreenTarget::read_pixels`].
+#[derive(Debug)]
+pub struct OffscreenTarget {
+    pub texture: wgpu::Texture,
+    pub size: PhysicalSize<u32>,
+}
+
+impl OffscreenTarget {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    pub fn new(device: &wgpu::Device, size: PhysicalSize<u32>) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Target"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: OffscreenTarget::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+
+        Self { texture, size }
+    }
+
+    pub fn read_pixels(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<RgbaImage, wgpu::BufferAsyncError> {
+        read_texture(device, queue, &self.texture, self.size)
+    }
+}
+
+/// Copies a 4 byte per pixel texture into a `RgbaImage`.
+///
+/// Rows in the staging buffer have to be aligned to `COPY_BYTES_PER_ROW_ALIGNMENT`,
+/// the padding is stripped before the image is built. Fails if the staging buffer can't be mapped,
+/// e.g. after the device was lost.
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    size: PhysicalSize<u32>,
+) -> Result<RgbaImage, wgpu::BufferAsyncError> {
+    const BYTES_PER_PIXEL: u32 = 4;
+
+    let unpadded_bytes_per_row = size.width * BYTES_PER_PIXEL;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded_bytes_per_row * size.height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Readback Encoder"),
+    });
+
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                rows_per_image: NonZeroU32::new(size.height),
+            },
+        },
+        wgpu::Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 1,
+        },
+    );
+
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let mapping = slice.map_async(wgpu::MapMode::Read);
+    device.poll(wgpu::Maintain::Wait);
+    pollster::block_on(mapping)?;
+
+    let pixels = {
+        let data = slice.get_mapped_range();
+        data.chunks(padded_bytes_per_row as usize)
+            .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+            .copied()
+            .collect::<Vec<u8>>()
+    };
+    buffer.unmap();
+
+    Ok(RgbaImage::from_raw(size.width, size.height, pixels).expect("Readback size mismatch"))
+}
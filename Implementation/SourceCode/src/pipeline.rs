@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use wgpu::{
+    BlendState, CompareFunction, DepthBiasState, Face, IndexFormat, PolygonMode, PrimitiveTopology,
+    RenderPipeline, StencilState, TextureFormat, VertexBufferLayout,
+};
+
+use crate::shaders::{catch_validation_error, ShaderDefs};
+
+/// Depth test of a [`PipelineKey`], the stencil is left at its default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DepthState {
+    pub format: TextureFormat,
+    pub write_enabled: bool,
+    pub compare: CompareFunction,
+    /// Constant depth bias, negative values pull the primitives towards the camera.
+    pub bias: i32,
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        Self {
+            format: TextureFormat::Depth24Plus,
+            write_enabled: true,
+            compare: CompareFunction::Less,
+            bias: 0,
+        }
+    }
+}
+
+/// How distances are stored in the depth buffer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DepthMode {
+    /// The near plane at 0 and the far plane at 1 in a `Depth24Plus` buffer.
+    #[default]
+    Standard,
+    /// The near plane at 1 and an infinitely far plane at 0 in a `Depth32Float` buffer.
+    /// Floats are most precise close to 0, which evens out the precision over the distance.
+    ReversedZ,
+}
+
+impl DepthMode {
+    pub fn format(self) -> TextureFormat {
+        match self {
+            DepthMode::Standard => TextureFormat::Depth24Plus,
+            DepthMode::ReversedZ => TextureFormat::Depth32Float,
+        }
+    }
+
+    /// The depth of the far plane, which the depth buffer is cleared to.
+    pub fn clear_value(self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::ReversedZ => 0.0,
+        }
+    }
+
+    /// `depth` written for standard depth in this mode: with its format,
+    /// and for reversed-Z with the comparison and the bias flipped.
+    pub fn depth_state(self, depth: DepthState) -> DepthState {
+        let depth = DepthState {
+            format: self.format(),
+            ..depth
+        };
+
+        match self {
+            DepthMode::Standard => depth,
+            DepthMode::ReversedZ => DepthState {
+                compare: match depth.compare {
+                    CompareFunction::Less => CompareFunction::Greater,
+                    CompareFunction::LessEqual => CompareFunction::GreaterEqual,
+                    CompareFunction::Greater => CompareFunction::Less,
+                    CompareFunction::GreaterEqual => CompareFunction::LessEqual,
+                    compare => compare,
+                },
+                bias: -depth.bias,
+                ..depth
+            },
+        }
+    }
+}
+
+/// The fixed-function state of a render pipeline, draws that differ in any of it need their own pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub topology: PrimitiveTopology,
+    /// Required by indexed strips, `None` for lists.
+    pub strip_index_format: Option<IndexFormat>,
+    pub polygon_mode: PolygonMode,
+    pub cull_mode: Option<Face>,
+    pub blend: Option<BlendState>,
+    pub depth: Option<DepthState>,
+    pub vertex_layout: VertexBufferLayout<'static>,
+}
+
+impl PipelineKey {
+    /// A key for indexed draws of `topology`, triangles are culled from the back and depth tested.
+    pub fn new(topology: PrimitiveTopology, vertex_layout: VertexBufferLayout<'static>) -> Self {
+        Self {
+            topology,
+            strip_index_format: topology.is_strip().then_some(IndexFormat::Uint32),
+            polygon_mode: PolygonMode::Fill,
+            cull_mode: match topology {
+                PrimitiveTopology::TriangleList | PrimitiveTopology::TriangleStrip => {
+                    Some(Face::Back)
+                }
+                _ => None,
+            },
+            blend: None,
+            depth: Some(DepthState::default()),
+            vertex_layout,
+        }
+    }
+
+    pub fn with_polygon_mode(mut self, polygon_mode: PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: Option<BlendState>) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: Option<DepthState>) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn primitive_state(&self) -> wgpu::PrimitiveState {
+        wgpu::PrimitiveState {
+            topology: self.topology,
+            strip_index_format: self.strip_index_format,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: self.cull_mode,
+            polygon_mode: self.polygon_mode,
+            unclipped_depth: false,
+            conservative: false,
+        }
+    }
+
+    pub fn depth_stencil_state(&self) -> Option<wgpu::DepthStencilState> {
+        self.depth.map(|depth| wgpu::DepthStencilState {
+            format: depth.format,
+            depth_write_enabled: depth.write_enabled,
+            depth_compare: depth.compare,
+            stencil: StencilState::default(),
+            bias: DepthBiasState {
+                constant: depth.bias,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct CachedPipeline {
+    pipeline: Option<RenderPipeline>,
+    /// Generations of the shaders the pipeline was last built with.
+    shader_generations: Option<Vec<u64>>,
+}
+
+/// Render pipelines of a renderer, built on first use and cached by their shader permutation and [`PipelineKey`].
+///
+/// A pipeline is rebuilt when the generation of one of its shaders changed,
+/// if the rebuild fails the error is logged and the last pipeline is kept.
+#[derive(Debug, Default)]
+pub struct PipelineCache {
+    pipelines: HashMap<ShaderDefs, HashMap<PipelineKey, CachedPipeline>>,
+}
+
+impl PipelineCache {
+    /// Builds the pipeline if it isn't cached or its shaders were recompiled since.
+    /// `create` is called in an error scope, so invalid pipelines are reported instead of panicking.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        defs: &ShaderDefs,
+        key: &PipelineKey,
+        shader_generations: &[u64],
+        create: impl FnOnce(&PipelineKey) -> RenderPipeline,
+    ) {
+        let cached = self
+            .pipelines
+            .entry(defs.clone())
+            .or_default()
+            .entry(key.clone())
+            .or_default();
+        if cached.shader_generations.as_deref() == Some(shader_generations) {
+            return;
+        }
+        cached.shader_generations = Some(shader_generations.to_vec());
+
+        match catch_validation_error(device, |_| create(key)) {
+            Ok(pipeline) => cached.pipeline = Some(pipeline),
+            Err(error) => log::error!(
+                "{error}\nKeeping the last pipeline of {defs} for {:?}",
+                key.topology
+            ),
+        }
+    }
+
+    /// The pipeline built by [`PipelineCache::prepare`], `None` if building it failed.
+    pub fn get(&self, defs: &ShaderDefs, key: &PipelineKey) -> Option<&RenderPipeline> {
+        self.pipelines.get(defs)?.get(key)?.pipeline.as_ref()
+    }
+}
@@ -0,0 +1,19 @@
+use crate::app::App;
+
+/// Bundles the systems and resources of a feature so it can be added to an [`App`] in one call.
+///
+/// ```ignore
+/// impl Plugin for GltfViewerPlugin {
+///     fn build(&self, app: &mut App) {
+///         app.add_system_to_stage(Stage::Startup, GLTFLoader::new(self.path.clone()))
+///             .add_render_node(GLTFRenderer::LABEL, GLTFRenderer::default());
+///     }
+/// }
+/// ```
+pub trait Plugin {
+    fn build(&self, app: &mut App);
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
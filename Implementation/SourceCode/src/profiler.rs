@@ -0,0 +1,668 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    sync::mpsc::{self, Receiver, Sender},
+    task::{Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use wgpu::{BufferAddress, Features, PipelineStatisticsTypes};
+
+use crate::{
+    app::{App, Context, System, World},
+    plugin::Plugin,
+    render::MainWorld,
+    schedule::{Stage, SystemDescriptor, SystemLabel},
+};
+
+/// Scopes a [`GpuProfiler`] can measure per frame, further scopes are ignored.
+pub const MAX_SCOPES: u32 = 32;
+
+/// Frames that can wait for their queries to be read back before profiling skips a frame.
+const READBACK_FRAMES: usize = 3;
+
+const STATISTICS: PipelineStatisticsTypes = PipelineStatisticsTypes::VERTEX_SHADER_INVOCATIONS
+    .union(PipelineStatisticsTypes::CLIPPER_INVOCATIONS)
+    .union(PipelineStatisticsTypes::CLIPPER_PRIMITIVES_OUT)
+    .union(PipelineStatisticsTypes::FRAGMENT_SHADER_INVOCATIONS);
+const STATISTICS_COUNT: u32 = 4;
+
+const QUERY_SIZE: BufferAddress = std::mem::size_of::<u64>() as BufferAddress;
+const TIMESTAMPS_SIZE: BufferAddress = 2 * MAX_SCOPES as BufferAddress * QUERY_SIZE;
+/// Statistics are resolved behind the timestamps, the offset has to be aligned.
+const STATISTICS_OFFSET: BufferAddress = TIMESTAMPS_SIZE
+    .div_ceil(wgpu::QUERY_RESOLVE_BUFFER_ALIGNMENT)
+    * wgpu::QUERY_RESOLVE_BUFFER_ALIGNMENT;
+const RESOLVE_SIZE: BufferAddress =
+    STATISTICS_OFFSET + (MAX_SCOPES * STATISTICS_COUNT) as BufferAddress * QUERY_SIZE;
+
+type Mapping = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+/// Where the milliseconds of a [`PassTiming`] come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingSource {
+    /// GPU timestamps written before and after the pass.
+    Gpu,
+    /// Time the CPU spent encoding the pass, used without `Features::TIMESTAMP_QUERY`.
+    Cpu,
+}
+
+/// Work the GPU did in a pass, requires `Features::PIPELINE_STATISTICS_QUERY`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineStatistics {
+    pub vertex_shader_invocations: u64,
+    /// Primitives that reached the clipper.
+    pub clipper_invocations: u64,
+    /// Primitives that survived clipping and were rasterized.
+    pub clipper_primitives_out: u64,
+    pub fragment_shader_invocations: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PassTiming {
+    pub label: &'static str,
+    pub milliseconds: f64,
+    pub source: TimingSource,
+    pub statistics: Option<PipelineStatistics>,
+}
+
+/// The measured passes of a frame. Inserted into the main world by the [`ProfilerPlugin`],
+/// GPU results trail the current frame by a few frames.
+#[derive(Debug, Clone, Default)]
+pub struct FrameProfile {
+    /// Index of the profiled frame, counted by the profiler.
+    pub frame: u64,
+    pub passes: Vec<PassTiming>,
+}
+
+impl FrameProfile {
+    pub fn pass(&self, label: &str) -> Option<&PassTiming> {
+        self.passes.iter().find(|pass| pass.label == label)
+    }
+
+    pub fn total_milliseconds(&self) -> f64 {
+        self.passes.iter().map(|pass| pass.milliseconds).sum()
+    }
+}
+
+/// A pass being measured, returned by [`GpuProfiler::begin_scope`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileScope {
+    index: u32,
+}
+
+#[derive(Debug, Clone)]
+struct ScopeRecord {
+    label: &'static str,
+    cpu_start: Instant,
+    cpu_time: Option<Duration>,
+    has_statistics: bool,
+}
+
+struct GpuQueries {
+    timestamps: Option<wgpu::QuerySet>,
+    statistics: Option<wgpu::QuerySet>,
+    resolve_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick.
+    timestamp_period: f32,
+}
+
+impl std::fmt::Debug for GpuQueries {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GpuQueries")
+            .field("timestamps", &self.timestamps.is_some())
+            .field("statistics", &self.statistics.is_some())
+            .field("timestamp_period", &self.timestamp_period)
+            .finish()
+    }
+}
+
+struct Readback {
+    buffer: wgpu::Buffer,
+    frame: u64,
+    scopes: Vec<ScopeRecord>,
+    mapping: Option<Mapping>,
+}
+
+impl std::fmt::Debug for Readback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Readback")
+            .field("frame", &self.frame)
+            .field("scopes", &self.scopes.len())
+            .field("is_mapping", &self.mapping.is_some())
+            .finish()
+    }
+}
+
+/// Render world resource that measures passes with timestamp and pipeline statistics queries.
+///
+/// The queries are resolved at the end of the frame and read back without stalling,
+/// the result shows up in [`GpuProfiler::latest`] once the GPU finished the frame.
+/// Without the query features only the CPU encoding time of each pass is measured.
+///
+/// ```ignore
+/// let scope = profiler.begin_scope("shadows", &mut encoder);
+/// {
+///     let mut pass = encoder.begin_render_pass(&descriptor);
+///     profiler.begin_statistics(&mut pass, scope);
+///     // ...
+///     profiler.end_statistics(&mut pass, scope);
+/// }
+/// profiler.end_scope(scope, &mut encoder);
+/// ```
+#[derive(Debug)]
+pub struct GpuProfiler {
+    queries: Option<GpuQueries>,
+    readbacks: Vec<Readback>,
+    scopes: RefCell<Vec<ScopeRecord>>,
+    frame: u64,
+    latest: Option<FrameProfile>,
+}
+
+impl GpuProfiler {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let features = device.features();
+        let timestamps = features.contains(Features::TIMESTAMP_QUERY).then(|| {
+            device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Profiler Timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count: 2 * MAX_SCOPES,
+            })
+        });
+        let statistics = features
+            .contains(Features::PIPELINE_STATISTICS_QUERY)
+            .then(|| {
+                device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("Profiler Statistics"),
+                    ty: wgpu::QueryType::PipelineStatistics(STATISTICS),
+                    count: MAX_SCOPES,
+                })
+            });
+
+        if timestamps.is_none() {
+            log::info!("GPU timestamps are not supported, profiling the CPU time of the passes");
+        }
+
+        let queries = (timestamps.is_some() || statistics.is_some()).then(|| GpuQueries {
+            timestamps,
+            statistics,
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Profiler Resolve Buffer"),
+                size: RESOLVE_SIZE,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            timestamp_period: queue.get_timestamp_period(),
+        });
+
+        let readbacks = match queries {
+            Some(_) => (0..READBACK_FRAMES)
+                .map(|_| Readback {
+                    buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Profiler Readback Buffer"),
+                        size: RESOLVE_SIZE,
+                        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                        mapped_at_creation: false,
+                    }),
+                    frame: 0,
+                    scopes: vec![],
+                    mapping: None,
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        Self {
+            queries,
+            readbacks,
+            scopes: RefCell::new(vec![]),
+            frame: 0,
+            latest: None,
+        }
+    }
+
+    pub fn timing_source(&self) -> TimingSource {
+        match self.queries {
+            Some(GpuQueries {
+                timestamps: Some(_),
+                ..
+            }) => TimingSource::Gpu,
+            _ => TimingSource::Cpu,
+        }
+    }
+
+    /// The newest frame whose results were read back.
+    pub fn latest(&self) -> Option<&FrameProfile> {
+        self.latest.as_ref()
+    }
+
+    /// Starts measuring the commands recorded into `encoder` from here on,
+    /// `None` once [`MAX_SCOPES`] scopes were started this frame.
+    pub fn begin_scope(
+        &self,
+        label: &'static str,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Option<ProfileScope> {
+        let mut scopes = self.scopes.borrow_mut();
+        let index = scopes.len() as u32;
+        if index >= MAX_SCOPES {
+            log::debug!("Not profiling `{label}`, more than {MAX_SCOPES} scopes in a frame");
+            return None;
+        }
+
+        if let Some(timestamps) = self.timestamps() {
+            encoder.write_timestamp(timestamps, 2 * index);
+        }
+
+        scopes.push(ScopeRecord {
+            label,
+            cpu_start: Instant::now(),
+            cpu_time: None,
+            has_statistics: false,
+        });
+
+        Some(ProfileScope { index })
+    }
+
+    pub fn end_scope(&self, scope: Option<ProfileScope>, encoder: &mut wgpu::CommandEncoder) {
+        let scope = match scope {
+            Some(scope) => scope,
+            None => return,
+        };
+
+        if let Some(timestamps) = self.timestamps() {
+            encoder.write_timestamp(timestamps, 2 * scope.index + 1);
+        }
+
+        if let Some(record) = self.scopes.borrow_mut().get_mut(scope.index as usize) {
+            record.cpu_time = Some(record.cpu_start.elapsed());
+        }
+    }
+
+    /// Starts counting the pipeline statistics of the pass, statistics can't be nested.
+    pub fn begin_statistics(&self, pass: &mut impl StatisticsPass, scope: Option<ProfileScope>) {
+        let (statistics, scope) = match (self.statistics(), scope) {
+            (Some(statistics), Some(scope)) => (statistics, scope),
+            _ => return,
+        };
+
+        pass.begin_statistics(statistics, scope.index);
+        if let Some(record) = self.scopes.borrow_mut().get_mut(scope.index as usize) {
+            record.has_statistics = true;
+        }
+    }
+
+    pub fn end_statistics(&self, pass: &mut impl StatisticsPass, scope: Option<ProfileScope>) {
+        if self.statistics().is_some() && scope.is_some() {
+            pass.end_statistics();
+        }
+    }
+
+    /// Collects the results of earlier frames that are ready and starts reading back this frame.
+    pub fn resolve(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.poll_readbacks(device);
+
+        let scopes = self.scopes.take();
+        self.frame += 1;
+        if scopes.is_empty() {
+            return;
+        }
+
+        let queries = match self.queries {
+            Some(ref queries) => queries,
+            None => {
+                self.latest = Some(cpu_profile(self.frame, &scopes));
+                return;
+            }
+        };
+
+        let readback = match self
+            .readbacks
+            .iter_mut()
+            .find(|readback| readback.mapping.is_none())
+        {
+            Some(readback) => readback,
+            None => {
+                log::debug!(
+                    "Skipping the profile of frame {}, the readbacks are busy",
+                    self.frame
+                );
+                return;
+            }
+        };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Profiler Resolve Encoder"),
+        });
+
+        let count = scopes.len() as u32;
+        if let Some(ref timestamps) = queries.timestamps {
+            encoder.resolve_query_set(timestamps, 0..2 * count, &queries.resolve_buffer, 0);
+        }
+        if let Some(ref statistics) = queries.statistics {
+            encoder.resolve_query_set(
+                statistics,
+                0..count,
+                &queries.resolve_buffer,
+                STATISTICS_OFFSET,
+            );
+        }
+        encoder.copy_buffer_to_buffer(
+            &queries.resolve_buffer,
+            0,
+            &readback.buffer,
+            0,
+            RESOLVE_SIZE,
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        readback.frame = self.frame;
+        readback.scopes = scopes;
+        readback.mapping = Some(Box::pin(
+            readback.buffer.slice(..).map_async(wgpu::MapMode::Read),
+        ));
+    }
+
+    fn timestamps(&self) -> Option<&wgpu::QuerySet> {
+        self.queries.as_ref().and_then(|q| q.timestamps.as_ref())
+    }
+
+    fn statistics(&self) -> Option<&wgpu::QuerySet> {
+        self.queries.as_ref().and_then(|q| q.statistics.as_ref())
+    }
+
+    fn poll_readbacks(&mut self, device: &wgpu::Device) {
+        let queries = match self.queries {
+            Some(ref queries) => queries,
+            None => return,
+        };
+
+        device.poll(wgpu::Maintain::Poll);
+        let mut cx = std::task::Context::from_waker(Waker::noop());
+
+        for readback in self.readbacks.iter_mut() {
+            let result = match readback.mapping {
+                Some(ref mut mapping) => match mapping.as_mut().poll(&mut cx) {
+                    Poll::Ready(result) => result,
+                    Poll::Pending => continue,
+                },
+                None => continue,
+            };
+            readback.mapping = None;
+            let scopes = std::mem::take(&mut readback.scopes);
+
+            if let Err(error) = result {
+                log::warn!(
+                    "Failed to read back the profile of frame {}, {error}",
+                    readback.frame
+                );
+                continue;
+            }
+
+            let profile = {
+                let data = readback.buffer.slice(..).get_mapped_range();
+                let values: &[u64] = bytemuck::cast_slice(&data);
+                gpu_profile(readback.frame, &scopes, values, queries)
+            };
+            readback.buffer.unmap();
+
+            // Readbacks can finish out of order
+            if self
+                .latest
+                .as_ref()
+                .is_none_or(|latest| latest.frame < profile.frame)
+            {
+                self.latest = Some(profile);
+            }
+        }
+    }
+}
+
+fn cpu_profile(frame: u64, scopes: &[ScopeRecord]) -> FrameProfile {
+    FrameProfile {
+        frame,
+        passes: scopes
+            .iter()
+            .filter_map(|scope| {
+                Some(PassTiming {
+                    label: scope.label,
+                    milliseconds: scope.cpu_time?.as_secs_f64() * 1000.0,
+                    source: TimingSource::Cpu,
+                    statistics: None,
+                })
+            })
+            .collect(),
+    }
+}
+
+fn gpu_profile(
+    frame: u64,
+    scopes: &[ScopeRecord],
+    values: &[u64],
+    queries: &GpuQueries,
+) -> FrameProfile {
+    let statistics_start = (STATISTICS_OFFSET / QUERY_SIZE) as usize;
+
+    let passes = scopes
+        .iter()
+        .enumerate()
+        .filter(|(_, scope)| scope.cpu_time.is_some())
+        .map(|(index, scope)| {
+            let (milliseconds, source) = match queries.timestamps {
+                Some(_) => {
+                    let ticks = values[2 * index + 1].saturating_sub(values[2 * index]);
+                    let nanoseconds = ticks as f64 * queries.timestamp_period as f64;
+                    (nanoseconds / 1_000_000.0, TimingSource::Gpu)
+                }
+                None => (
+                    scope.cpu_time.unwrap_or_default().as_secs_f64() * 1000.0,
+                    TimingSource::Cpu,
+                ),
+            };
+
+            // Values are written in the order of the `PipelineStatisticsTypes` bits
+            let statistics = (queries.statistics.is_some() && scope.has_statistics).then(|| {
+                let start = statistics_start + index * STATISTICS_COUNT as usize;
+                PipelineStatistics {
+                    vertex_shader_invocations: values[start],
+                    clipper_invocations: values[start + 1],
+                    clipper_primitives_out: values[start + 2],
+                    fragment_shader_invocations: values[start + 3],
+                }
+            });
+
+            PassTiming {
+                label: scope.label,
+                milliseconds,
+                source,
+                statistics,
+            }
+        })
+        .collect();
+
+    FrameProfile { frame, passes }
+}
+
+/// Passes that can count pipeline statistics.
+pub trait StatisticsPass {
+    fn begin_statistics(&mut self, query_set: &wgpu::QuerySet, index: u32);
+    fn end_statistics(&mut self);
+}
+
+impl<'a> StatisticsPass for wgpu::RenderPass<'a> {
+    fn begin_statistics(&mut self, query_set: &wgpu::QuerySet, index: u32) {
+        self.begin_pipeline_statistics_query(query_set, index);
+    }
+
+    fn end_statistics(&mut self) {
+        self.end_pipeline_statistics_query();
+    }
+}
+
+impl<'a> StatisticsPass for wgpu::ComputePass<'a> {
+    fn begin_statistics(&mut self, query_set: &wgpu::QuerySet, index: u32) {
+        self.begin_pipeline_statistics_query(query_set, index);
+    }
+
+    fn end_statistics(&mut self) {
+        self.end_pipeline_statistics_query();
+    }
+}
+
+/// Profiles the passes of the primary window and logs the result periodically.
+///
+/// Nodes of the render graph are measured under their label, other systems measure their passes
+/// through the [`GpuProfiler`] resource of the render world. The latest [`FrameProfile`] is available as resource of the main world.
+#[derive(Debug, Clone)]
+pub struct ProfilerPlugin {
+    /// `None` disables logging.
+    pub log_interval: Option<Duration>,
+}
+
+impl Default for ProfilerPlugin {
+    fn default() -> Self {
+        Self {
+            log_interval: Some(Duration::from_secs(1)),
+        }
+    }
+}
+
+impl Plugin for ProfilerPlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, profiles) = mpsc::channel();
+        app.add_system(
+            SystemDescriptor::new(ProfileExtractor { profiles })
+                .in_stage(Stage::Extract)
+                .label(ProfileExtractor::LABEL),
+        )
+        .add_system(
+            SystemDescriptor::new(ProfilerResolver::new(sender))
+                .in_stage(Stage::Present)
+                .label(ProfilerResolver::LABEL),
+        );
+
+        if let Some(interval) = self.log_interval {
+            app.add_system(
+                SystemDescriptor::new(ProfileLogger::new(interval)).in_stage(Stage::PostUpdate),
+            );
+        }
+    }
+}
+
+/// Creates the [`GpuProfiler`] in the render world, resolves its queries after every frame
+/// and sends every new [`FrameProfile`] to the [`ProfileExtractor`].
+#[derive(Debug)]
+pub struct ProfilerResolver {
+    profiles: Sender<FrameProfile>,
+    last_sent: Option<u64>,
+}
+
+impl ProfilerResolver {
+    pub const LABEL: SystemLabel = "profiler_resolver";
+
+    pub fn new(profiles: Sender<FrameProfile>) -> Self {
+        Self {
+            profiles,
+            last_sent: None,
+        }
+    }
+}
+
+impl System for ProfilerResolver {
+    fn init(&mut self, context: &Context, world: &mut World) {
+        world.insert_resource(GpuProfiler::new(&context.device, &context.queue));
+    }
+
+    fn run(&mut self, context: &Context, world: &mut World) {
+        let profiler = match world.get_resource_mut::<GpuProfiler>() {
+            Some(profiler) => profiler,
+            None => return,
+        };
+        profiler.resolve(&context.device, &context.queue);
+
+        if let Some(profile) = profiler.latest() {
+            if self.last_sent != Some(profile.frame) {
+                self.last_sent = Some(profile.frame);
+                // The extractor is gone once the app shuts down
+                let _ = self.profiles.send(profile.clone());
+            }
+        }
+    }
+}
+
+/// Copies the latest [`FrameProfile`] the render thread sent into the main world.
+///
+/// The [`GpuProfiler`] lives in the persistent render world on the render thread,
+/// which the `Extract` stage can't reach.
+#[derive(Debug)]
+pub struct ProfileExtractor {
+    profiles: Receiver<FrameProfile>,
+}
+
+impl ProfileExtractor {
+    pub const LABEL: SystemLabel = "profile_extractor";
+}
+
+impl System for ProfileExtractor {
+    fn run(&mut self, _context: &Context, world: &mut World) {
+        let profile = match self.profiles.try_iter().last() {
+            Some(profile) => profile,
+            None => return,
+        };
+
+        if let Some(main_world) = world.get_resource_mut::<MainWorld>() {
+            main_world.insert_resource(profile);
+        }
+    }
+}
+
+/// Logs the [`FrameProfile`] at the info level.
+#[derive(Debug)]
+pub struct ProfileLogger {
+    interval: Duration,
+    last_log: Instant,
+}
+
+impl ProfileLogger {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_log: Instant::now(),
+        }
+    }
+}
+
+impl System for ProfileLogger {
+    fn run(&mut self, _context: &Context, world: &mut World) {
+        if self.last_log.elapsed() < self.interval {
+            return;
+        }
+
+        let profile = match world.get_resource::<FrameProfile>() {
+            Some(profile) => profile,
+            None => return,
+        };
+        self.last_log = Instant::now();
+
+        for pass in profile.passes.iter() {
+            let source = match pass.source {
+                TimingSource::Gpu => "gpu",
+                TimingSource::Cpu => "cpu",
+            };
+
+            match pass.statistics {
+                Some(statistics) => log::info!(
+                    "{}: {:.3} ms ({source}), {} vertex invocations, {} of {} primitives rasterized, {} fragment invocations",
+                    pass.label,
+                    pass.milliseconds,
+                    statistics.vertex_shader_invocations,
+                    statistics.clipper_primitives_out,
+                    statistics.clipper_invocations,
+                    statistics.fragment_shader_invocations,
+                ),
+                None => log::info!("{}: {:.3} ms ({source})", pass.label, pass.milliseconds),
+            }
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, TryRecvError},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use image::{imageops::FilterType, RgbaImage};
+use serde::Deserialize;
+use thiserror::Error;
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    app::{App, Context, System, World},
+    ecs::EventReader,
+    loader::{AnimationLoop, ModelLoaded},
+    plugin::Plugin,
+    schedule::{Stage, SystemDescriptor, SystemLabel},
+};
+
+#[derive(Error, Debug)]
+pub enum RecorderError {
+    #[error("failed to write the GIF: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to encode the GIF: {0}")]
+    Encoding(#[from] gif::EncodingError),
+    #[error("{width}x{height} exceeds the maximum GIF size of 65535x65535")]
+    TooLarge { width: u32, height: u32 },
+    #[error("the recording has no frames")]
+    NoFrames,
+}
+
+/// How a turntable GIF is recorded, read from the `gif` section of the config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GifSettings {
+    /// Number of frames to capture.
+    pub frames: u32,
+    /// Delay between the frames in hundredths of a second, the unit GIF uses.
+    pub delay: u16,
+    /// Output width, `None` keeps the window width.
+    pub width: Option<u32>,
+    /// Output height, `None` keeps the aspect ratio of the window.
+    pub height: Option<u32>,
+    /// Enables the [`AnimationLoop`] while recording so the camera orbits the model.
+    pub orbit: bool,
+    /// Sampling speed of the color quantization, from 1 (best) to [`GifSettings::MAX_SPEED`] (fastest).
+    pub speed: i32,
+}
+
+impl Default for GifSettings {
+    fn default() -> Self {
+        Self {
+            frames: 120,
+            delay: 4,
+            width: Some(480),
+            height: None,
+            orbit: true,
+            speed: 10,
+        }
+    }
+}
+
+impl GifSettings {
+    /// The fastest quantization the encoder supports.
+    pub const MAX_SPEED: i32 = 30;
+
+    /// Size of the GIF for frames of the given size.
+    pub fn output_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        let scaled = |size: u32, from: u32, to: u32| {
+            (size as u64 * to as u64 / from.max(1) as u64).max(1) as u32
+        };
+
+        match (self.width, self.height) {
+            (Some(out_width), Some(out_height)) => (out_width, out_height),
+            (Some(out_width), None) => (out_width, scaled(height, width, out_width)),
+            (None, Some(out_height)) => (scaled(width, height, out_height), out_height),
+            (None, None) => (width, height),
+        }
+    }
+}
+
+/// Main world resource to start recordings from systems.
+#[derive(Debug, Default)]
+pub struct GifRecorder {
+    requested: Option<PathBuf>,
+    is_recording: bool,
+}
+
+impl GifRecorder {
+    /// Starts recording with the next frame, ignored while a recording is running.
+    pub fn record(&mut self, path: impl Into<PathBuf>) {
+        if !self.is_recording {
+            self.requested = Some(path.into());
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.is_recording || self.requested.is_some()
+    }
+}
+
+/// Records turntable GIFs of the primary window when `key` is pressed or through the [`GifRecorder`] resource.
+#[derive(Debug, Clone)]
+pub struct GifRecorderPlugin {
+    pub settings: GifSettings,
+    pub key: VirtualKeyCode,
+    /// Where the recordings started with `key` are saved.
+    pub directory: PathBuf,
+    /// Records into this file as soon as the model was loaded.
+    pub record_on_load: Option<PathBuf>,
+}
+
+impl Default for GifRecorderPlugin {
+    fn default() -> Self {
+        Self {
+            settings: GifSettings::default(),
+            key: VirtualKeyCode::F10,
+            directory: PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/screenshots")),
+            record_on_load: None,
+        }
+    }
+}
+
+impl Plugin for GifRecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(GifRecorder::default())
+            .add_event::<ModelLoaded>()
+            .add_system(
+                SystemDescriptor::new(GifCapture::new(self))
+                    .in_stage(Stage::PostUpdate)
+                    .label(GifCapture::LABEL),
+            );
+    }
+}
+
+#[derive(Debug)]
+struct Recording {
+    path: PathBuf,
+    frames: Vec<RgbaImage>,
+    pending: VecDeque<Receiver<RgbaImage>>,
+    requested: u32,
+    /// Restored once the recording is done.
+    animation: Option<AnimationLoop>,
+}
+
+/// Captures the frames of a recording and encodes them on a worker thread.
+#[derive(Debug)]
+pub struct GifCapture {
+    settings: GifSettings,
+    key: VirtualKeyCode,
+    directory: PathBuf,
+    record_on_load: Option<PathBuf>,
+    model_loaded: EventReader<ModelLoaded>,
+    recording: Option<Recording>,
+}
+
+impl GifCapture {
+    pub const LABEL: SystemLabel = "gif_capture";
+
+    fn new(plugin: &GifRecorderPlugin) -> Self {
+        Self {
+            settings: plugin.settings.clone(),
+            key: plugin.key,
+            directory: plugin.directory.clone(),
+            record_on_load: plugin.record_on_load.clone(),
+            model_loaded: EventReader::default(),
+            recording: None,
+        }
+    }
+
+    fn start(&mut self, path: PathBuf, world: &mut World) {
+        log::info!(
+            "Recording {} frames into `{}`",
+            self.settings.frames,
+            path.display()
+        );
+
+        let animation = match self.settings.orbit {
+            true => world.get_resource_mut::<AnimationLoop>().map(|animation| {
+                let previous = *animation;
+                *animation = AnimationLoop::Enable;
+                previous
+            }),
+            false => None,
+        };
+
+        self.recording = Some(Recording {
+            path,
+            frames: Vec::with_capacity(self.settings.frames as usize),
+            pending: VecDeque::new(),
+            requested: 0,
+            animation,
+        });
+    }
+
+    fn finish(&mut self, world: &mut World) {
+        let recording = match self.recording.take() {
+            Some(recording) => recording,
+            None => return,
+        };
+
+        if let (Some(previous), Some(animation)) = (
+            recording.animation,
+            world.get_resource_mut::<AnimationLoop>(),
+        ) {
+            *animation = previous;
+        }
+        if let Some(recorder) = world.get_resource_mut::<GifRecorder>() {
+            recorder.is_recording = false;
+        }
+
+        let settings = self.settings.clone();
+        thread::spawn(
+            move || match encode_gif(&recording.path, recording.frames, &settings) {
+                Ok(()) => log::info!("Saved the recording `{}`", recording.path.display()),
+                Err(error) => log::error!(
+                    "Failed to save the recording `{}`, {error}",
+                    recording.path.display()
+                ),
+            },
+        );
+    }
+
+    /// `turntable-<unix millis>.gif` in the recording directory.
+    fn next_path(&self) -> PathBuf {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis());
+
+        self.directory.join(format!("turntable-{millis}.gif"))
+    }
+}
+
+impl System for GifCapture {
+    fn process_events(&mut self, event: &Event<()>, context: &Context, world: &mut World) {
+        if let Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(keycode),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                },
+            window_id,
+        } = event
+        {
+            if *keycode == self.key && Some(*window_id) == context.window_id() {
+                let path = self.next_path();
+                if let Some(recorder) = world.get_resource_mut::<GifRecorder>() {
+                    recorder.record(path);
+                }
+            }
+        }
+    }
+
+    fn run(&mut self, context: &Context, world: &mut World) {
+        if self.model_loaded.read_world(world).next().is_some() {
+            if let (Some(path), Some(recorder)) = (
+                self.record_on_load.take(),
+                world.get_resource_mut::<GifRecorder>(),
+            ) {
+                recorder.record(path);
+            }
+        }
+
+        let requested = world
+            .get_resource_mut::<GifRecorder>()
+            .and_then(|recorder| {
+                let requested = recorder.requested.take()?;
+                recorder.is_recording = true;
+                Some(requested)
+            });
+        if let Some(path) = requested {
+            self.start(path, world);
+        }
+
+        let settings = &self.settings;
+        let recording = match self.recording {
+            Some(ref mut recording) => recording,
+            None => return,
+        };
+
+        if recording.requested < settings.frames {
+            recording.pending.push_back(context.capture_frame());
+            recording.requested += 1;
+        }
+
+        // Frames arrive in the order they were requested
+        while let Some(receiver) = recording.pending.front() {
+            match receiver.try_recv() {
+                Ok(frame) => {
+                    let (width, height) = settings.output_size(frame.dimensions());
+                    let frame = match frame.dimensions() == (width, height) {
+                        true => frame,
+                        false => {
+                            image::imageops::resize(&frame, width, height, FilterType::Triangle)
+                        }
+                    };
+                    recording.frames.push(frame);
+                }
+                Err(TryRecvError::Empty) => break,
+                // The frame wasn't rendered, request another one
+                Err(TryRecvError::Disconnected) => recording.requested -= 1,
+            }
+            recording.pending.pop_front();
+        }
+
+        if recording.frames.len() as u32 >= settings.frames {
+            self.finish(world);
+        }
+    }
+}
+
+/// Quantizes the frames and writes them as looping GIF, frames are scaled to the size of the first one.
+pub fn encode_gif(
+    path: &Path,
+    frames: Vec<RgbaImage>,
+    settings: &GifSettings,
+) -> Result<(), RecorderError> {
+    let (width, height) = match frames.first() {
+        Some(frame) => frame.dimensions(),
+        None => return Err(RecorderError::NoFrames),
+    };
+    let too_large = || RecorderError::TooLarge { width, height };
+    let gif_width = u16::try_from(width).map_err(|_| too_large())?;
+    let gif_height = u16::try_from(height).map_err(|_| too_large())?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = gif::Encoder::new(file, gif_width, gif_height, &[])?;
+    // The encoder panics outside of this range
+    let speed = settings.speed.clamp(1, GifSettings::MAX_SPEED);
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    for frame in frames {
+        let frame = match frame.dimensions() == (width, height) {
+            true => frame,
+            false => image::imageops::resize(&frame, width, height, FilterType::Triangle),
+        };
+
+        let mut pixels = frame.into_raw();
+        let mut gif_frame = gif::Frame::from_rgba_speed(gif_width, gif_height, &mut pixels, speed);
+        gif_frame.delay = settings.delay;
+        encoder.write_frame(&gif_frame)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,245 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::mpsc::{self, Receiver, SyncSender, TryRecvError},
+    thread::{self, JoinHandle},
+};
+
+use winit::{event_loop::EventLoopProxy, window::WindowId};
+
+use crate::{
+    app::{Context, FrameStatus, World},
+    schedule::Schedule,
+    time::FrameLimiter,
+};
+
+/// The world the app simulates, inserted into the render world while the `Extract` stage runs.
+///
+/// ```ignore
+/// fn run(&mut self, _context: &Context, world: &mut World) {
+///     let meshes = world.get_resource::<MainWorld>().unwrap().query::<&MeshBuffer>().iter().cloned().collect();
+///     world.insert_resource(ExtractedMeshes(meshes));
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct MainWorld(World);
+
+impl MainWorld {
+    pub(crate) fn new(world: World) -> Self {
+        Self(world)
+    }
+
+    pub(crate) fn into_inner(self) -> World {
+        self.0
+    }
+}
+
+impl Deref for MainWorld {
+    type Target = World;
+
+    fn deref(&self) -> &World {
+        &self.0
+    }
+}
+
+impl DerefMut for MainWorld {
+    fn deref_mut(&mut self) -> &mut World {
+        &mut self.0
+    }
+}
+
+/// The render side of a window: a context sharing the surface of the main thread's context
+/// and the systems of its `Render` and `Present` stages.
+#[derive(Debug)]
+pub(crate) struct RenderWindow {
+    pub context: Context,
+    pub schedule: Schedule,
+}
+
+/// Everything the renderer needs for a frame, sent to the render thread after `Extract`.
+#[derive(Debug)]
+pub(crate) struct RenderJob {
+    /// The frame world the `Extract` stage wrote the resources of this frame into.
+    pub world: World,
+    /// Latest configuration of every window, applied before its frame is acquired.
+    pub configs: Vec<(Option<WindowId>, wgpu::SurfaceConfiguration)>,
+    /// Windows opened since the last frame, their systems are initialized with the render world.
+    pub added: Vec<RenderWindow>,
+    pub removed: Vec<WindowId>,
+    /// Target of the [`FrameLimiter`] resource of the main world, applied on the render thread.
+    pub target_fps: Option<f64>,
+}
+
+/// Returned once a [`RenderJob`] was drawn, hands the emptied frame world back to the main thread.
+#[derive(Debug)]
+pub(crate) struct RenderResult {
+    pub world: World,
+    pub statuses: Vec<(Option<WindowId>, FrameStatus)>,
+}
+
+/// Draws the render world into every window, either inline or on the [`RenderThread`].
+///
+/// The render world persists across frames, it keeps what the render systems create in `init`
+/// like the shader cache. The resources of every extracted frame world are moved into it before drawing.
+#[derive(Debug, Default)]
+pub(crate) struct Renderer {
+    windows: Vec<RenderWindow>,
+    world: World,
+}
+
+impl Renderer {
+    pub fn render(&mut self, job: RenderJob) -> RenderResult {
+        let RenderJob {
+            world: mut frame_world,
+            configs,
+            added,
+            removed,
+            ..
+        } = job;
+        let world = &mut self.world;
+        world.append_resources(&mut frame_world);
+
+        self.windows.retain(|window| {
+            !window
+                .context
+                .window_id()
+                .is_some_and(|id| removed.contains(&id))
+        });
+
+        added.into_iter().for_each(|mut window| {
+            window.schedule.startup(&window.context, world);
+            self.windows.push(window);
+        });
+
+        let statuses = self
+            .windows
+            .iter_mut()
+            .map(|window| {
+                let id = window.context.window_id();
+                if let Some((_, config)) = configs.iter().find(|(window_id, _)| *window_id == id) {
+                    window.context.sync_config(config);
+                }
+
+                let status = window
+                    .schedule
+                    .run_render_stages(&mut window.context, world);
+                (id, status)
+            })
+            .collect();
+
+        RenderResult {
+            world: frame_world,
+            statuses,
+        }
+    }
+
+    /// The render context of a window, `None` is the offscreen target of a headless app.
+    pub fn context(&self, window_id: Option<WindowId>) -> Option<&Context> {
+        self.windows
+            .iter()
+            .map(|window| &window.context)
+            .find(|context| context.window_id() == window_id)
+    }
+}
+
+/// Runs the [`Renderer`] on its own thread so slow draws don't block the event loop.
+///
+/// Frames are double-buffered: the main thread owns [`RenderThread::FRAMES_IN_FLIGHT`] frame worlds
+/// and extracts the next frame into a free one while the render thread still draws the previous one.
+/// Once both are in flight the main thread skips its redraws until the render thread
+/// hands a world back and wakes the event loop, it never blocks on a draw.
+/// The [`FrameLimiter`] caps the frames on the render thread for the same reason.
+#[derive(Debug)]
+pub(crate) struct RenderThread {
+    jobs: Option<SyncSender<RenderJob>>,
+    results: Receiver<RenderResult>,
+    handle: Option<JoinHandle<()>>,
+    in_flight: usize,
+}
+
+impl RenderThread {
+    /// Frames extracted but not drawn yet, one drawn and one waiting.
+    pub const FRAMES_IN_FLIGHT: usize = 2;
+
+    /// Spawns the thread, `proxy` wakes the event loop whenever a frame was drawn.
+    pub fn spawn(mut renderer: Renderer, proxy: EventLoopProxy<()>) -> std::io::Result<Self> {
+        let (jobs, job_receiver) = mpsc::sync_channel::<RenderJob>(Self::FRAMES_IN_FLIGHT);
+        let (result_sender, results) = mpsc::sync_channel(Self::FRAMES_IN_FLIGHT);
+
+        let handle = thread::Builder::new()
+            .name("render".to_owned())
+            .spawn(move || {
+                let mut limiter = FrameLimiter::default();
+                for job in job_receiver {
+                    limiter.set_target_fps(job.target_fps);
+                    let result = renderer.render(job);
+                    limiter.wait();
+
+                    if result_sender.send(result).is_err() {
+                        break;
+                    }
+                    // The event loop is gone during shutdown, which waits for the results directly
+                    let _ = proxy.send_event(());
+                }
+                log::debug!("Render thread stopped");
+            })?;
+
+        Ok(Self {
+            jobs: Some(jobs),
+            results,
+            handle: Some(handle),
+            in_flight: 0,
+        })
+    }
+
+    /// Hands the job to the render thread without waiting for it to be drawn.
+    /// Returns `false` if the render thread stopped.
+    pub fn submit(&mut self, job: RenderJob) -> bool {
+        debug_assert!(
+            self.in_flight < Self::FRAMES_IN_FLIGHT,
+            "every frame world is already in flight"
+        );
+
+        let submitted = match self.jobs {
+            Some(ref jobs) => jobs.send(job).is_ok(),
+            None => false,
+        };
+        self.in_flight += submitted as usize;
+        submitted
+    }
+
+    /// The next drawn frame if there is one, `Disconnected` once the render thread stopped.
+    pub fn try_receive(&mut self) -> Result<RenderResult, TryRecvError> {
+        let result = self.results.try_recv()?;
+        self.in_flight -= 1;
+        Ok(result)
+    }
+
+    /// Blocks until the oldest frame in flight was drawn, `None` if there is none
+    /// or the render thread stopped.
+    pub fn wait(&mut self) -> Option<RenderResult> {
+        if self.in_flight == 0 {
+            return None;
+        }
+
+        self.in_flight -= 1;
+        self.results.recv().ok()
+    }
+
+    /// Finishes the frames in flight and joins the thread.
+    pub fn shutdown(&mut self) {
+        while self.wait().is_some() {}
+        self.jobs = None;
+
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::error!("The render thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for RenderThread {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
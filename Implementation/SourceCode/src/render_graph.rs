@@ -0,0 +1,879 @@
+//! Frames drawn as a graph of passes.
+//!
+//! Every [`Node`] declares the attachments it draws into and the resources it reads and writes,
+//! the graph orders the nodes by these declarations, allocates the transient textures,
+//! begins every pass with the right clears and loads and submits the frame in one command buffer.
+//!
+//! ```ignore
+//! impl Node for ShadowNode {
+//!     fn declare(&self, node: &mut NodeBuilder) {
+//!         let info = TextureInfo {
+//!             format: TextureFormat::Depth32Float,
+//!             size: TextureSize::Fixed { width: 2048, height: 2048 },
+//!             multisampled: false,
+//!         };
+//!         node.create_texture(SHADOW_MAP, info).depth_attachment(SHADOW_MAP, 1.0);
+//!     }
+//!     // ...
+//! }
+//!
+//! app.add_render_node(ShadowNode::LABEL, ShadowNode::default())
+//!     .add_render_node(MainNode::LABEL, MainNode::default());
+//! ```
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use thiserror::Error;
+use wgpu::{LoadOp, Operations, TextureFormat, TextureUsages};
+
+use crate::{
+    app::{Context, System, World},
+    profiler::GpuProfiler,
+    schedule::SystemLabel,
+};
+
+/// Names a node of a [`RenderGraph`].
+pub type NodeLabel = &'static str;
+
+/// Names a texture or buffer the nodes of a [`RenderGraph`] share.
+pub type ResourceLabel = &'static str;
+
+#[derive(Error, Debug)]
+pub enum RenderGraphError {
+    #[error("the node `{0}` was added twice")]
+    DuplicateNode(NodeLabel),
+    #[error("the texture `{0}` is created with different descriptions")]
+    ConflictingTexture(ResourceLabel),
+    #[error("the node `{node}` attaches `{resource}`, which is neither created by a node nor the surface")]
+    UnknownAttachment {
+        node: NodeLabel,
+        resource: ResourceLabel,
+    },
+    #[error("the node `{node}` reads `{resource}`, which no node writes")]
+    ReadBeforeWrite {
+        node: NodeLabel,
+        resource: ResourceLabel,
+    },
+    #[error("the nodes {0:?} depend on each other")]
+    Cycle(Vec<NodeLabel>),
+}
+
+/// Size of a transient texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureSize {
+    /// The size of the frame, the texture is recreated when the window is resized.
+    Frame,
+    Fixed {
+        width: u32,
+        height: u32,
+    },
+}
+
+/// A texture the graph allocates for its nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureInfo {
+    pub format: TextureFormat,
+    pub size: TextureSize,
+    /// Uses the MSAA sample count of the frame, required to be attached together with the surface.
+    pub multisampled: bool,
+}
+
+impl TextureInfo {
+    /// A texture of the frame's size and sample count, like a depth buffer.
+    pub fn frame(format: TextureFormat) -> Self {
+        Self {
+            format,
+            size: TextureSize::Frame,
+            multisampled: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DepthAttachment {
+    label: ResourceLabel,
+    clear: f32,
+    /// `false` attaches the texture read-only, for depth testing without writing.
+    write: bool,
+}
+
+/// Collects the declarations of a [`Node`].
+#[derive(Debug, Default)]
+pub struct NodeBuilder {
+    textures: Vec<(ResourceLabel, TextureInfo)>,
+    color_attachments: Vec<(ResourceLabel, wgpu::Color)>,
+    depth_attachment: Option<DepthAttachment>,
+    reads: Vec<ResourceLabel>,
+    writes: Vec<ResourceLabel>,
+}
+
+impl NodeBuilder {
+    /// Lets the graph allocate the texture, nodes refer to it by `label`.
+    pub fn create_texture(&mut self, label: ResourceLabel, info: TextureInfo) -> &mut Self {
+        self.textures.push((label, info));
+        self
+    }
+
+    /// Draws into a created texture or [`RenderGraph::SURFACE`].
+    /// The first pass of the frame that draws into it clears it to `clear`, the following ones load it.
+    pub fn color_attachment(&mut self, label: ResourceLabel, clear: wgpu::Color) -> &mut Self {
+        self.color_attachments.push((label, clear));
+        self.writes.push(label);
+        self
+    }
+
+    /// Depth tests against and writes into a created texture, cleared to `clear` by the first pass.
+    pub fn depth_attachment(&mut self, label: ResourceLabel, clear: f32) -> &mut Self {
+        self.depth_attachment = Some(DepthAttachment {
+            label,
+            clear,
+            write: true,
+        });
+        self.writes.push(label);
+        self
+    }
+
+    /// Depth tests against the depth written by earlier nodes without changing it.
+    pub fn depth_test(&mut self, label: ResourceLabel) -> &mut Self {
+        self.depth_attachment = Some(DepthAttachment {
+            label,
+            clear: 0.0,
+            write: false,
+        });
+        self.reads.push(label);
+        self
+    }
+
+    /// The node runs after every node writing `label`.
+    /// Resources the graph doesn't create, like the buffers of the nodes, only order the nodes.
+    pub fn read(&mut self, label: ResourceLabel) -> &mut Self {
+        self.reads.push(label);
+        self
+    }
+
+    /// The node runs before every node reading `label`, and in insertion order with other writers.
+    pub fn write(&mut self, label: ResourceLabel) -> &mut Self {
+        self.writes.push(label);
+        self
+    }
+
+    fn has_attachments(&self) -> bool {
+        !self.color_attachments.is_empty() || self.depth_attachment.is_some()
+    }
+
+    fn touches(&self, label: ResourceLabel) -> bool {
+        self.reads.contains(&label) || self.writes.contains(&label)
+    }
+}
+
+/// What a node can reach while recording its pass.
+pub struct NodeContext<'a> {
+    pub context: &'a Context,
+    /// The render world.
+    pub world: &'a World,
+}
+
+/// A pass of a [`RenderGraph`].
+pub trait Node: std::fmt::Debug + Send {
+    /// Declares the attachments and resources of the node, called once when the graph is built.
+    fn declare(&self, node: &mut NodeBuilder);
+
+    /// Called once with the render world before the first frame.
+    fn init(&mut self, _context: &Context, _world: &mut World) {}
+
+    /// Called every frame before any node records its pass, to build pipelines and upload buffers.
+    fn prepare(&mut self, _context: &Context, _world: &mut World) {}
+
+    /// Inactive nodes are skipped this frame, the attachments are cleared by the next node drawing into them.
+    fn is_active(&self, _world: &World) -> bool {
+        true
+    }
+
+    /// Records the pass the graph began with the declared attachments.
+    fn run<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, node: &NodeContext<'a>);
+}
+
+/// How a pass begins and ends with each of its attachments.
+#[derive(Debug, Clone, PartialEq)]
+struct PassOperations {
+    /// One per color attachment, in the order they were declared.
+    color: Vec<Operations<wgpu::Color>>,
+    /// `None` without a depth attachment or with a read-only one.
+    depth: Option<Operations<f32>>,
+}
+
+#[derive(Debug)]
+struct GraphNode {
+    label: NodeLabel,
+    node: Box<dyn Node>,
+    declaration: NodeBuilder,
+}
+
+/// Size, format and sample count a transient texture was created with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TextureKey {
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    sample_count: u32,
+}
+
+#[derive(Debug)]
+struct TransientTexture {
+    info: TextureInfo,
+    allocated: Option<(TextureKey, wgpu::TextureView)>,
+}
+
+impl TransientTexture {
+    /// Recreates the texture if it doesn't fit the frame of `context` anymore.
+    fn prepare(&mut self, label: ResourceLabel, context: &Context) {
+        let (width, height) = match self.info.size {
+            TextureSize::Frame => (context.config.width, context.config.height),
+            TextureSize::Fixed { width, height } => (width, height),
+        };
+        let key = TextureKey {
+            width: width.max(1),
+            height: height.max(1),
+            format: self.info.format,
+            sample_count: match self.info.multisampled {
+                true => context.sample_count,
+                false => 1,
+            },
+        };
+        if matches!(self.allocated, Some((allocated, _)) if allocated == key) {
+            return;
+        }
+
+        let view = context
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: key.width,
+                    height: key.height,
+                    ..Default::default()
+                },
+                mip_level_count: 1,
+                sample_count: key.sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: key.format,
+                usage: TextureUsages::RENDER_ATTACHMENT,
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.allocated = Some((key, view));
+    }
+
+    fn view(&self) -> &wgpu::TextureView {
+        &self
+            .allocated
+            .as_ref()
+            .expect("transient textures are allocated before the nodes run")
+            .1
+    }
+}
+
+/// Runs the nodes of a window in the `Render` stage, see the [module](self) docs.
+///
+/// Nodes of the primary window are added with [`App::add_render_node`](crate::app::App::add_render_node),
+/// other windows add a graph as their system.
+/// Every node is measured by the [`GpuProfiler`] if it's present, under the label of the node.
+#[derive(Debug, Default)]
+pub struct RenderGraph {
+    nodes: Vec<GraphNode>,
+    /// Indices into `nodes` in the order they run.
+    order: Vec<usize>,
+    textures: HashMap<ResourceLabel, TransientTexture>,
+    /// Rendered into instead of the surface with MSAA, then resolved into the frame.
+    msaa_surface: Option<TransientTexture>,
+}
+
+impl RenderGraph {
+    pub const LABEL: SystemLabel = "render_graph";
+    /// The frame of the window, or the offscreen target of a headless app.
+    pub const SURFACE: ResourceLabel = "surface";
+
+    pub fn add_node(&mut self, label: NodeLabel, node: impl Node + 'static) -> &mut Self {
+        self.nodes.push(GraphNode {
+            label,
+            node: Box::new(node),
+            declaration: NodeBuilder::default(),
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Collects the declarations of the nodes, checks them and orders the nodes.
+    pub fn build(&mut self) -> Result<(), RenderGraphError> {
+        let mut labels = HashSet::new();
+        for graph_node in &mut self.nodes {
+            if !labels.insert(graph_node.label) {
+                return Err(RenderGraphError::DuplicateNode(graph_node.label));
+            }
+
+            graph_node.declaration = NodeBuilder::default();
+            graph_node.node.declare(&mut graph_node.declaration);
+        }
+
+        self.textures = self.transient_textures()?;
+        for graph_node in &self.nodes {
+            let declaration = &graph_node.declaration;
+            let attachments = declaration
+                .color_attachments
+                .iter()
+                .map(|(label, _)| *label)
+                .chain(declaration.depth_attachment.map(|depth| depth.label));
+
+            for resource in attachments {
+                if resource != Self::SURFACE && !self.textures.contains_key(resource) {
+                    return Err(RenderGraphError::UnknownAttachment {
+                        node: graph_node.label,
+                        resource,
+                    });
+                }
+            }
+        }
+
+        self.order = self.sorted_nodes()?;
+        Ok(())
+    }
+
+    /// The textures created by the nodes.
+    fn transient_textures(
+        &self,
+    ) -> Result<HashMap<ResourceLabel, TransientTexture>, RenderGraphError> {
+        let mut textures = HashMap::<ResourceLabel, TransientTexture>::new();
+
+        for (label, info) in self
+            .nodes
+            .iter()
+            .flat_map(|graph_node| &graph_node.declaration.textures)
+        {
+            match textures.get(label) {
+                Some(texture) if texture.info != *info => {
+                    return Err(RenderGraphError::ConflictingTexture(label))
+                }
+                Some(_) => {}
+                None => {
+                    textures.insert(
+                        label,
+                        TransientTexture {
+                            info: *info,
+                            allocated: None,
+                        },
+                    );
+                }
+            }
+        }
+
+        Ok(textures)
+    }
+
+    /// Orders the nodes so writers of a resource run before its readers, otherwise in insertion order.
+    fn sorted_nodes(&self) -> Result<Vec<usize>, RenderGraphError> {
+        let count = self.nodes.len();
+        let mut dependents = vec![vec![]; count];
+        let mut dependencies = vec![0; count];
+        let mut depend = |from: usize, to: usize| {
+            if from != to && !dependents[from].contains(&to) {
+                dependents[from].push(to);
+                dependencies[to] += 1;
+            }
+        };
+
+        let resources = self
+            .nodes
+            .iter()
+            .flat_map(|graph_node| {
+                let declaration = &graph_node.declaration;
+                declaration.reads.iter().chain(&declaration.writes).copied()
+            })
+            .collect::<BTreeSet<_>>();
+
+        for resource in resources {
+            let writers = (0..count)
+                .filter(|&index| self.nodes[index].declaration.writes.contains(&resource))
+                .collect::<Vec<_>>();
+            let readers = (0..count)
+                .filter(|&index| {
+                    let declaration = &self.nodes[index].declaration;
+                    declaration.reads.contains(&resource) && !declaration.writes.contains(&resource)
+                })
+                .collect::<Vec<_>>();
+
+            if writers.is_empty() && self.textures.contains_key(resource) {
+                return Err(RenderGraphError::ReadBeforeWrite {
+                    node: self.nodes[readers[0]].label,
+                    resource,
+                });
+            }
+
+            writers.windows(2).for_each(|pair| depend(pair[0], pair[1]));
+            for &writer in &writers {
+                readers.iter().for_each(|&reader| depend(writer, reader));
+            }
+        }
+
+        // Kahn's algorithm, picking the earliest added node that is ready keeps the insertion order
+        let mut ready = (0..count)
+            .filter(|&index| dependencies[index] == 0)
+            .collect::<BTreeSet<_>>();
+        let mut order = Vec::with_capacity(count);
+        while let Some(index) = ready.pop_first() {
+            order.push(index);
+            for &dependent in &dependents[index] {
+                dependencies[dependent] -= 1;
+                if dependencies[dependent] == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+
+        match order.len() == count {
+            true => Ok(order),
+            false => Err(RenderGraphError::Cycle(
+                (0..count)
+                    .filter(|index| !order.contains(index))
+                    .map(|index| self.nodes[index].label)
+                    .collect(),
+            )),
+        }
+    }
+
+    /// The operations of the passes of the `active` nodes, in the same order.
+    ///
+    /// Attachments are cleared by the first pass drawing into them and loaded by the following ones,
+    /// contents no later pass uses are discarded.
+    fn pass_operations(&self, active: &[usize], is_multisampled: bool) -> Vec<PassOperations> {
+        let mut initialized = HashSet::new();
+
+        active
+            .iter()
+            .enumerate()
+            .map(|(position, &index)| {
+                let declaration = &self.nodes[index].declaration;
+                let is_used_later = |label: ResourceLabel| {
+                    active[position + 1..]
+                        .iter()
+                        .any(|&later| self.nodes[later].declaration.touches(label))
+                };
+
+                let color = declaration
+                    .color_attachments
+                    .iter()
+                    .map(|&(label, clear)| Operations {
+                        load: match initialized.insert(label) {
+                            true => LoadOp::Clear(clear),
+                            false => LoadOp::Load,
+                        },
+                        // The frame is presented, with MSAA it keeps the resolved result
+                        store: is_used_later(label) || (label == Self::SURFACE && !is_multisampled),
+                    })
+                    .collect();
+
+                let depth = declaration.depth_attachment.and_then(|depth| {
+                    let load = match initialized.insert(depth.label) {
+                        true => LoadOp::Clear(depth.clear),
+                        false => LoadOp::Load,
+                    };
+                    depth.write.then(|| Operations {
+                        load,
+                        store: is_used_later(depth.label),
+                    })
+                });
+
+                PassOperations { color, depth }
+            })
+            .collect()
+    }
+
+    fn encode(&self, active: &[usize], context: &Context, world: &World) {
+        let frame = match context.frame() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let profiler = world.get_resource::<GpuProfiler>();
+        let node_context = NodeContext { context, world };
+
+        let mut encoder = context
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Graph Encoder"),
+            });
+
+        let operations = self.pass_operations(active, self.msaa_surface.is_some());
+        for (&index, operations) in active.iter().zip(operations) {
+            let graph_node = &self.nodes[index];
+            let declaration = &graph_node.declaration;
+            if !declaration.has_attachments() {
+                continue;
+            }
+
+            let color_attachments = declaration
+                .color_attachments
+                .iter()
+                .zip(operations.color)
+                .map(|(&(label, _), ops)| {
+                    let (view, resolve_target) = match label {
+                        Self::SURFACE => match self.msaa_surface {
+                            Some(ref msaa) => (msaa.view(), Some(&frame.view)),
+                            None => (&frame.view, None),
+                        },
+                        label => (self.textures[label].view(), None),
+                    };
+
+                    wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target,
+                        ops,
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let depth_stencil_attachment =
+                declaration
+                    .depth_attachment
+                    .map(|depth| wgpu::RenderPassDepthStencilAttachment {
+                        view: self.textures[depth.label].view(),
+                        // Without operations the depth is attached read-only
+                        depth_ops: operations.depth,
+                        stencil_ops: None,
+                    });
+
+            let scope =
+                profiler.and_then(|profiler| profiler.begin_scope(graph_node.label, &mut encoder));
+            {
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(graph_node.label),
+                    color_attachments: &color_attachments,
+                    depth_stencil_attachment,
+                });
+
+                if let Some(profiler) = profiler {
+                    profiler.begin_statistics(&mut pass, scope);
+                }
+                graph_node.node.run(&mut pass, &node_context);
+                if let Some(profiler) = profiler {
+                    profiler.end_statistics(&mut pass, scope);
+                }
+            }
+            if let Some(profiler) = profiler {
+                profiler.end_scope(scope, &mut encoder);
+            }
+        }
+
+        context.queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+impl System for RenderGraph {
+    fn init(&mut self, context: &Context, world: &mut World) {
+        if let Err(error) = self.build() {
+            // Draws nothing rather than running the nodes in an unchecked order
+            log::error!("the render graph is invalid, nothing is drawn: {error}");
+            self.order.clear();
+            self.textures.clear();
+            return;
+        }
+
+        for graph_node in &mut self.nodes {
+            graph_node.node.init(context, world);
+        }
+    }
+
+    fn run(&mut self, context: &Context, world: &mut World) {
+        for &index in &self.order {
+            self.nodes[index].node.prepare(context, world);
+        }
+
+        let active = self
+            .order
+            .iter()
+            .copied()
+            .filter(|&index| self.nodes[index].node.is_active(world))
+            .collect::<Vec<_>>();
+
+        for (label, texture) in &mut self.textures {
+            texture.prepare(label, context);
+        }
+        self.msaa_surface = match context.sample_count > 1 {
+            true => {
+                let mut msaa = self.msaa_surface.take().unwrap_or(TransientTexture {
+                    info: TextureInfo::frame(context.config.format),
+                    allocated: None,
+                });
+                msaa.info.format = context.config.format;
+                msaa.prepare("MSAA Surface", context);
+                Some(msaa)
+            }
+            false => None,
+        };
+
+        self.encode(&active, context, world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEPTH: ResourceLabel = "depth";
+    const SHADOW_MAP: ResourceLabel = "shadow_map";
+    const BLACK: wgpu::Color = wgpu::Color::BLACK;
+
+    type Declare = fn(&mut NodeBuilder);
+
+    /// A node that only declares, the graph is never run.
+    #[derive(Debug)]
+    struct Declared(Declare);
+
+    impl Node for Declared {
+        fn declare(&self, node: &mut NodeBuilder) {
+            (self.0)(node)
+        }
+
+        fn run<'a>(&'a self, _pass: &mut wgpu::RenderPass<'a>, _node: &NodeContext<'a>) {}
+    }
+
+    fn graph(nodes: &[(NodeLabel, Declare)]) -> RenderGraph {
+        let mut graph = RenderGraph::default();
+        for &(label, declare) in nodes {
+            graph.add_node(label, Declared(declare));
+        }
+        graph
+    }
+
+    fn order(graph: &RenderGraph) -> Vec<NodeLabel> {
+        graph
+            .order
+            .iter()
+            .map(|&index| graph.nodes[index].label)
+            .collect()
+    }
+
+    fn depth(node: &mut NodeBuilder) {
+        node.create_texture(DEPTH, TextureInfo::frame(TextureFormat::Depth32Float))
+            .depth_attachment(DEPTH, 1.0);
+    }
+
+    fn shadow_map(node: &mut NodeBuilder) {
+        let info = TextureInfo {
+            format: TextureFormat::Depth32Float,
+            size: TextureSize::Fixed {
+                width: 1024,
+                height: 1024,
+            },
+            multisampled: false,
+        };
+        node.create_texture(SHADOW_MAP, info)
+            .depth_attachment(SHADOW_MAP, 1.0);
+    }
+
+    fn clear(clear: wgpu::Color, store: bool) -> Operations<wgpu::Color> {
+        Operations {
+            load: LoadOp::Clear(clear),
+            store,
+        }
+    }
+
+    fn load<V>(store: bool) -> Operations<V> {
+        Operations {
+            load: LoadOp::Load,
+            store,
+        }
+    }
+
+    #[test]
+    fn writers_run_before_readers() {
+        let mut graph = graph(&[
+            ("main", |node| {
+                node.color_attachment(RenderGraph::SURFACE, BLACK)
+                    .read(SHADOW_MAP);
+            }),
+            ("shadows", shadow_map),
+            ("ui", |node| {
+                node.color_attachment(RenderGraph::SURFACE, BLACK);
+            }),
+        ]);
+
+        graph.build().unwrap();
+        assert_eq!(order(&graph), ["shadows", "main", "ui"]);
+    }
+
+    #[test]
+    fn unrelated_nodes_keep_their_order() {
+        let mut graph = graph(&[
+            ("b", |node| {
+                node.write("b");
+            }),
+            ("a", |node| {
+                node.write("a");
+            }),
+            ("reads_a", |node| {
+                node.read("a");
+            }),
+        ]);
+
+        graph.build().unwrap();
+        assert_eq!(order(&graph), ["b", "a", "reads_a"]);
+    }
+
+    #[test]
+    fn invalid_graphs_are_rejected() {
+        let mut duplicate = graph(&[("a", depth), ("a", depth)]);
+        assert!(matches!(
+            duplicate.build(),
+            Err(RenderGraphError::DuplicateNode("a"))
+        ));
+
+        let mut conflicting = graph(&[
+            ("a", depth),
+            ("b", |node| {
+                node.create_texture(DEPTH, TextureInfo::frame(TextureFormat::Depth24Plus));
+            }),
+        ]);
+        assert!(matches!(
+            conflicting.build(),
+            Err(RenderGraphError::ConflictingTexture(DEPTH))
+        ));
+
+        let mut unknown = graph(&[("a", |node| {
+            node.color_attachment("missing", BLACK);
+        })]);
+        assert!(matches!(
+            unknown.build(),
+            Err(RenderGraphError::UnknownAttachment {
+                node: "a",
+                resource: "missing"
+            })
+        ));
+
+        let mut unwritten = graph(&[("a", |node| {
+            node.create_texture(DEPTH, TextureInfo::frame(TextureFormat::Depth32Float))
+                .depth_test(DEPTH);
+        })]);
+        assert!(matches!(
+            unwritten.build(),
+            Err(RenderGraphError::ReadBeforeWrite {
+                node: "a",
+                resource: DEPTH
+            })
+        ));
+
+        let mut cycle = graph(&[
+            ("free", depth),
+            ("a", |node| {
+                node.read("x").write("y");
+            }),
+            ("b", |node| {
+                node.read("y").write("x");
+            }),
+        ]);
+        match cycle.build() {
+            Err(RenderGraphError::Cycle(nodes)) => assert_eq!(nodes, ["a", "b"]),
+            result => panic!("expected a cycle, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn first_writers_clear_and_later_nodes_load() {
+        let mut graph = graph(&[
+            ("opaque", |node| {
+                node.color_attachment(RenderGraph::SURFACE, BLACK);
+                depth(node);
+            }),
+            ("transparent", |node| {
+                node.create_texture(DEPTH, TextureInfo::frame(TextureFormat::Depth32Float))
+                    .color_attachment(RenderGraph::SURFACE, wgpu::Color::WHITE)
+                    .depth_test(DEPTH);
+            }),
+            ("ui", |node| {
+                node.color_attachment(RenderGraph::SURFACE, wgpu::Color::WHITE);
+            }),
+        ]);
+        graph.build().unwrap();
+
+        assert_eq!(
+            graph.pass_operations(&graph.order, false),
+            [
+                PassOperations {
+                    color: vec![clear(BLACK, true)],
+                    depth: Some(Operations {
+                        load: LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                },
+                // The read-only depth has no operations and isn't used afterwards
+                PassOperations {
+                    color: vec![load(true)],
+                    depth: None,
+                },
+                PassOperations {
+                    color: vec![load(true)],
+                    depth: None,
+                },
+            ]
+        );
+
+        // Without the opaque pass the next node drawing into the surface clears it
+        assert_eq!(
+            graph.pass_operations(&graph.order[1..], false)[0].color,
+            [clear(wgpu::Color::WHITE, true)]
+        );
+    }
+
+    #[test]
+    fn unused_contents_are_discarded() {
+        let mut graph = graph(&[
+            ("shadows", shadow_map),
+            ("main", |node| {
+                node.color_attachment(RenderGraph::SURFACE, BLACK)
+                    .read(SHADOW_MAP);
+                depth(node);
+            }),
+        ]);
+        graph.build().unwrap();
+
+        let operations = graph.pass_operations(&graph.order, false);
+        assert_eq!(
+            operations[0].depth,
+            Some(Operations {
+                load: LoadOp::Clear(1.0),
+                store: true,
+            })
+        );
+        assert_eq!(
+            operations[1].depth,
+            Some(Operations {
+                load: LoadOp::Clear(1.0),
+                store: false,
+            })
+        );
+    }
+
+    #[test]
+    fn multisampled_surfaces_are_only_resolved() {
+        let mut graph = graph(&[
+            ("main", |node| {
+                node.color_attachment(RenderGraph::SURFACE, BLACK);
+            }),
+            ("ui", |node| {
+                node.color_attachment(RenderGraph::SURFACE, BLACK);
+            }),
+        ]);
+        graph.build().unwrap();
+
+        let colors = |is_multisampled| {
+            graph
+                .pass_operations(&graph.order, is_multisampled)
+                .into_iter()
+                .map(|operations| operations.color)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(colors(false), [vec![clear(BLACK, true)], vec![load(true)]]);
+        // Only the last pass keeps the MSAA texture, in the frame it resolved into
+        assert_eq!(colors(true), [vec![clear(BLACK, true)], vec![load(false)]]);
+    }
+}
@@ -0,0 +1,481 @@
+use std::{collections::BTreeMap, mem};
+
+use winit::event::Event;
+
+use crate::{
+    app::{Context, FrameStatus, System, World},
+    render::MainWorld,
+    time::Time,
+};
+
+/// The stages a frame is split into, they run in the order they are declared.
+///
+/// `Startup` only runs once after every system was initialized,
+/// `FixedUpdate` runs zero or more times per frame at the fixed timestep of the [`Time`] resource,
+/// the remaining stages run once on every frame.
+///
+/// The stages up to `PostUpdate` simulate the main world on the main thread.
+/// `Extract` copies what the renderer needs from the [`MainWorld`] into the render world,
+/// `Render` and `Present` then draw the render world on the render thread
+/// while the main thread already simulates the next frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum Stage {
+    Startup,
+    PreUpdate,
+    FixedUpdate,
+    #[default]
+    Update,
+    PostUpdate,
+    Extract,
+    Render,
+    Present,
+}
+
+impl Stage {
+    /// Stages that run on the render thread.
+    pub const RENDER: [Stage; 2] = [Stage::Render, Stage::Present];
+}
+
+pub type SystemLabel = &'static str;
+
+pub type RunCondition = Box<dyn FnMut(&World) -> bool + Send>;
+
+/// A system plus where and when it runs.
+///
+/// ```ignore
+/// SystemDescriptor::new(CameraUpdater::default())
+///     .in_stage(Stage::Update)
+///     .label("camera")
+///     .before("draw")
+///     .run_if(|world| !world.is_empty());
+/// ```
+pub struct SystemDescriptor {
+    system: Box<dyn System>,
+    stage: Stage,
+    labels: Vec<SystemLabel>,
+    before: Vec<SystemLabel>,
+    after: Vec<SystemLabel>,
+    run_conditions: Vec<RunCondition>,
+}
+
+impl SystemDescriptor {
+    pub fn new(system: impl System + 'static) -> Self {
+        Self::from_boxed(Box::new(system))
+    }
+
+    pub fn from_boxed(system: Box<dyn System>) -> Self {
+        Self {
+            system,
+            stage: Stage::default(),
+            labels: vec![],
+            before: vec![],
+            after: vec![],
+            run_conditions: vec![],
+        }
+    }
+
+    pub fn in_stage(mut self, stage: Stage) -> Self {
+        self.stage = stage;
+        self
+    }
+
+    pub fn label(mut self, label: SystemLabel) -> Self {
+        self.labels.push(label);
+        self
+    }
+
+    /// Runs this system before every system of the same stage labeled `label`.
+    pub fn before(mut self, label: SystemLabel) -> Self {
+        self.before.push(label);
+        self
+    }
+
+    /// Runs this system after every system of the same stage labeled `label`.
+    pub fn after(mut self, label: SystemLabel) -> Self {
+        self.after.push(label);
+        self
+    }
+
+    /// The system is skipped for a frame if any of its conditions returns `false`.
+    pub fn run_if(mut self, condition: impl FnMut(&World) -> bool + Send + 'static) -> Self {
+        self.run_conditions.push(Box::new(condition));
+        self
+    }
+}
+
+impl std::fmt::Debug for SystemDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SystemDescriptor")
+            .field("system", &self.system)
+            .field("stage", &self.stage)
+            .field("labels", &self.labels)
+            .field("before", &self.before)
+            .field("after", &self.after)
+            .field("run_conditions", &self.run_conditions.len())
+            .finish()
+    }
+}
+
+impl<S: System + 'static> From<Box<S>> for SystemDescriptor {
+    fn from(system: Box<S>) -> Self {
+        SystemDescriptor::from_boxed(system)
+    }
+}
+
+impl From<Box<dyn System>> for SystemDescriptor {
+    fn from(system: Box<dyn System>) -> Self {
+        SystemDescriptor::from_boxed(system)
+    }
+}
+
+/// A system together with the change tick it last ran at.
+#[derive(Debug)]
+struct SystemEntry {
+    descriptor: SystemDescriptor,
+    last_run: u32,
+}
+
+impl SystemEntry {
+    fn should_run(&mut self, world: &World) -> bool {
+        self.descriptor
+            .run_conditions
+            .iter_mut()
+            .all(|condition| condition(world))
+    }
+
+    fn init(&mut self, context: &Context, world: &mut World) {
+        let system = &mut self.descriptor.system;
+        world.run_system(&mut self.last_run, |world| system.init(context, world));
+    }
+
+    fn process_events(&mut self, event: &Event<()>, context: &Context, world: &mut World) {
+        let system = &mut self.descriptor.system;
+        world.run_system(&mut self.last_run, |world| {
+            system.process_events(event, context, world)
+        });
+    }
+
+    fn run(&mut self, context: &Context, world: &mut World) {
+        if !self.should_run(world) {
+            return;
+        }
+
+        let system = &mut self.descriptor.system;
+        world.run_system(&mut self.last_run, |world| system.run(context, world));
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Schedule {
+    stages: BTreeMap<Stage, Vec<SystemEntry>>,
+    is_sorted: bool,
+}
+
+impl Schedule {
+    pub fn add_system(&mut self, descriptor: impl Into<SystemDescriptor>) {
+        let descriptor = descriptor.into();
+
+        self.stages
+            .entry(descriptor.stage)
+            .or_default()
+            .push(SystemEntry {
+                descriptor,
+                last_run: 0,
+            });
+        self.is_sorted = false;
+    }
+
+    /// Initializes every system and runs the `Startup` stage once.
+    pub fn startup(&mut self, context: &Context, world: &mut World) {
+        self.sort();
+
+        self.systems_mut()
+            .for_each(|system| system.init(context, world));
+
+        self.run_stage(Stage::Startup, context, world);
+    }
+
+    pub fn run_stage(&mut self, stage: Stage, context: &Context, world: &mut World) {
+        self.sort();
+
+        if let Some(systems) = self.stages.get_mut(&stage) {
+            systems
+                .iter_mut()
+                .for_each(|system| system.run(context, world));
+        }
+    }
+
+    /// Advances the frame time and runs the simulation stages up to `PostUpdate`.
+    pub fn run_frame(&mut self, context: &Context, world: &mut World) {
+        if let Some(time) = world.get_resource_mut::<Time>() {
+            time.update();
+        }
+
+        self.run_stage(Stage::PreUpdate, context, world);
+
+        while world
+            .get_resource_mut::<Time>()
+            .is_some_and(Time::expend_fixed_step)
+        {
+            self.run_stage(Stage::FixedUpdate, context, world);
+        }
+
+        self.run_update_stages(context, world);
+    }
+
+    /// Runs `Update` and `PostUpdate` without advancing the time,
+    /// used by the windows that follow the frame time of the primary window.
+    pub fn run_update_stages(&mut self, context: &Context, world: &mut World) {
+        self.run_stage(Stage::Update, context, world);
+        self.run_stage(Stage::PostUpdate, context, world);
+    }
+
+    /// Runs the `Extract` stage on the render world, the systems reach the main world
+    /// through the [`MainWorld`] resource.
+    pub fn run_extract(
+        &mut self,
+        context: &Context,
+        main_world: &mut World,
+        render_world: &mut World,
+    ) {
+        render_world.insert_resource(MainWorld::new(mem::take(main_world)));
+
+        self.run_stage(Stage::Extract, context, render_world);
+
+        if let Some(world) = render_world.remove_resource::<MainWorld>() {
+            *main_world = world.into_inner();
+        }
+    }
+
+    /// Acquires the frame, runs `Render` and `Present`, fulfills the capture requests and presents the frame.
+    /// Both stages are skipped if no frame could be acquired.
+    pub fn run_render_stages(&mut self, context: &mut Context, world: &mut World) -> FrameStatus {
+        let status = context.acquire_frame();
+        if status.is_rendered() {
+            self.run_stage(Stage::Render, context, world);
+            self.run_stage(Stage::Present, context, world);
+            context.capture_frame_requests();
+            context.present_frame();
+        }
+
+        status
+    }
+
+    /// Moves the systems of the [`Stage::RENDER`] stages into their own schedule for the render thread.
+    pub fn split_render_stages(&mut self) -> Schedule {
+        let stages = Stage::RENDER
+            .into_iter()
+            .filter_map(|stage| self.stages.remove_entry(&stage))
+            .collect();
+
+        Schedule {
+            stages,
+            is_sorted: false,
+        }
+    }
+
+    pub fn process_events(&mut self, event: &Event<()>, context: &Context, world: &mut World) {
+        self.sort();
+
+        self.systems_mut()
+            .for_each(|system| system.process_events(event, context, world));
+    }
+
+    fn systems_mut(&mut self) -> impl Iterator<Item = &mut SystemEntry> {
+        self.stages.values_mut().flatten()
+    }
+
+    fn sort(&mut self) {
+        if self.is_sorted {
+            return;
+        }
+
+        self.stages.iter_mut().for_each(|(stage, systems)| {
+            systems
+                .iter()
+                .flat_map(|system| system.descriptor.before.iter().chain(&system.descriptor.after))
+                .filter(|label| {
+                    !systems
+                        .iter()
+                        .any(|system| system.descriptor.labels.contains(label))
+                })
+                .for_each(|label| {
+                    log::warn!("{stage:?} has no system labeled `{label}`, the constraint is ignored")
+                });
+
+            let order = topological_order(systems).unwrap_or_else(|| {
+                log::error!(
+                    "{stage:?} has cyclic system ordering constraints, its systems run in the order they were added"
+                );
+                (0..systems.len()).collect()
+            });
+
+            let mut entries = systems.drain(..).map(Some).collect::<Vec<_>>();
+            *systems = order
+                .into_iter()
+                .map(|index| entries[index].take().unwrap())
+                .collect();
+        });
+
+        self.is_sorted = true;
+    }
+}
+
+/// Orders the systems by their `before`/`after` labels, unconstrained systems keep their insertion order.
+/// Returns `None` if the constraints contain a cycle.
+fn topological_order(systems: &[SystemEntry]) -> Option<Vec<usize>> {
+    let len = systems.len();
+    let mut edges = vec![vec![]; len];
+    let mut in_degree = vec![0_usize; len];
+
+    let has_label = |index: usize, label: &SystemLabel| -> bool {
+        systems[index].descriptor.labels.contains(label)
+    };
+
+    for (index, system) in systems.iter().enumerate() {
+        for other in (0..len).filter(|other| *other != index) {
+            let runs_before = system
+                .descriptor
+                .before
+                .iter()
+                .any(|label| has_label(other, label));
+            let runs_after = system
+                .descriptor
+                .after
+                .iter()
+                .any(|label| has_label(other, label));
+
+            if runs_before {
+                edges[index].push(other);
+                in_degree[other] += 1;
+            }
+            if runs_after {
+                edges[other].push(index);
+                in_degree[index] += 1;
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(len);
+    let mut visited = vec![false; len];
+
+    while order.len() < len {
+        let next = (0..len).find(|index| !visited[*index] && in_degree[*index] == 0)?;
+
+        visited[next] = true;
+        order.push(next);
+        edges[next].iter().for_each(|other| in_degree[*other] -= 1);
+    }
+
+    Some(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Noop;
+
+    impl System for Noop {}
+
+    fn system(label: SystemLabel) -> SystemDescriptor {
+        SystemDescriptor::new(Noop).label(label)
+    }
+
+    /// The first label of every system of `stage`, in the order they run.
+    fn labels(schedule: &mut Schedule, stage: Stage) -> Vec<SystemLabel> {
+        schedule.sort();
+        schedule.stages.get(&stage).map_or(vec![], |systems| {
+            systems
+                .iter()
+                .map(|system| system.descriptor.labels[0])
+                .collect()
+        })
+    }
+
+    #[test]
+    fn unconstrained_systems_keep_their_order() {
+        let mut schedule = Schedule::default();
+        schedule.add_system(system("a"));
+        schedule.add_system(system("b"));
+        schedule.add_system(system("c"));
+
+        assert_eq!(labels(&mut schedule, Stage::Update), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn constraints_order_the_systems() {
+        let mut schedule = Schedule::default();
+        schedule.add_system(system("draw").after("camera"));
+        schedule.add_system(system("input").before("camera"));
+        schedule.add_system(system("camera"));
+        schedule.add_system(system("ui").after("draw").before("missing"));
+
+        assert_eq!(
+            labels(&mut schedule, Stage::Update),
+            ["input", "camera", "draw", "ui"]
+        );
+
+        // Systems added later are sorted in again
+        schedule.add_system(system("physics").before("input"));
+        assert_eq!(
+            labels(&mut schedule, Stage::Update),
+            ["physics", "input", "camera", "draw", "ui"]
+        );
+    }
+
+    #[test]
+    fn constraints_only_apply_within_a_stage() {
+        let mut schedule = Schedule::default();
+        schedule.add_system(system("late").in_stage(Stage::PostUpdate));
+        schedule.add_system(system("early").after("late"));
+
+        assert_eq!(labels(&mut schedule, Stage::Update), ["early"]);
+        assert_eq!(labels(&mut schedule, Stage::PostUpdate), ["late"]);
+    }
+
+    #[test]
+    fn cycles_have_no_order() {
+        let mut schedule = Schedule::default();
+        schedule.add_system(system("a").before("b"));
+        schedule.add_system(system("b").before("c"));
+        schedule.add_system(system("c").before("a"));
+        schedule.add_system(system("d"));
+
+        assert!(topological_order(&schedule.stages[&Stage::Update]).is_none());
+    }
+
+    #[test]
+    fn sorting_a_cycle_keeps_the_insertion_order() {
+        let mut schedule = Schedule::default();
+        schedule.add_system(system("a").after("b"));
+        schedule.add_system(system("b").after("a"));
+        schedule.add_system(system("c").before("missing"));
+
+        assert_eq!(labels(&mut schedule, Stage::Update), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn render_stages_are_split_off() {
+        let mut schedule = Schedule::default();
+        schedule.add_system(system("update"));
+        schedule.add_system(system("extract").in_stage(Stage::Extract));
+        schedule.add_system(system("present").in_stage(Stage::Present));
+        schedule.add_system(system("draw").in_stage(Stage::Render).after("prepare"));
+        schedule.add_system(system("prepare").in_stage(Stage::Render));
+
+        let mut render = schedule.split_render_stages();
+
+        assert_eq!(
+            schedule.stages.keys().copied().collect::<Vec<_>>(),
+            [Stage::Update, Stage::Extract]
+        );
+        assert_eq!(
+            render.stages.keys().copied().collect::<Vec<_>>(),
+            Stage::RENDER
+        );
+        assert_eq!(labels(&mut render, Stage::Render), ["prepare", "draw"]);
+        assert_eq!(labels(&mut render, Stage::Present), ["present"]);
+    }
+}
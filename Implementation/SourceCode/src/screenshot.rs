@@ -0,0 +1,82 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    app::{App, Context, System, World},
+    plugin::Plugin,
+    schedule::SystemDescriptor,
+};
+
+/// Saves a screenshot of the window when `key` is pressed, see [`Context::request_screenshot`].
+#[derive(Debug, Clone)]
+pub struct ScreenshotPlugin {
+    pub key: VirtualKeyCode,
+    pub directory: PathBuf,
+}
+
+impl Default for ScreenshotPlugin {
+    fn default() -> Self {
+        Self {
+            key: VirtualKeyCode::F12,
+            directory: PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/screenshots")),
+        }
+    }
+}
+
+impl Plugin for ScreenshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            SystemDescriptor::new(ScreenshotHotkey {
+                key: self.key,
+                directory: self.directory.clone(),
+            })
+            .label(ScreenshotHotkey::LABEL),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct ScreenshotHotkey {
+    key: VirtualKeyCode,
+    directory: PathBuf,
+}
+
+impl ScreenshotHotkey {
+    pub const LABEL: &'static str = "screenshot_hotkey";
+
+    /// `screenshot-<unix millis>.png` in the screenshot directory.
+    fn next_path(&self) -> PathBuf {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis());
+
+        self.directory.join(format!("screenshot-{millis}.png"))
+    }
+}
+
+impl System for ScreenshotHotkey {
+    fn process_events(&mut self, event: &Event<()>, context: &Context, _world: &mut World) {
+        if let Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(keycode),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                },
+            window_id,
+        } = event
+        {
+            if *keycode == self.key && Some(*window_id) == context.window_id() {
+                context.request_screenshot(self.next_path());
+            }
+        }
+    }
+}
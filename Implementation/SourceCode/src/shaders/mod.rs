@@ -0,0 +1,402 @@
+pub mod preprocessor;
+pub mod validation;
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use thiserror::Error;
+
+use crate::{
+    app::{App, Context, System, World},
+    plugin::Plugin,
+    render_graph::RenderGraph,
+    schedule::{Stage, SystemDescriptor, SystemLabel},
+    ASSETS_PATH,
+};
+
+pub use preprocessor::{preprocess, Preprocessed, ShaderDefs};
+
+#[derive(Error, Debug)]
+pub enum ShaderError {
+    #[error("failed to read `{}`: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{file}:{line}: {message}")]
+    Preprocess {
+        file: String,
+        line: usize,
+        message: String,
+    },
+    #[error("failed to parse `{name}`:\n{message}")]
+    Parse { name: String, message: String },
+    #[error("`{name}` is invalid: {message}")]
+    Validation { name: String, message: String },
+    #[error("failed to create the pipeline: {0}")]
+    Pipeline(#[from] wgpu::Error),
+}
+
+/// Parses and validates WGSL with naga, so broken shaders are reported as errors
+/// instead of reaching the device's error handler, which panics.
+pub fn validate_wgsl(name: &str, source: &str) -> Result<naga::Module, ShaderError> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|error| ShaderError::Parse {
+        name: name.to_owned(),
+        message: error.emit_to_string(source),
+    })?;
+
+    validator()
+        .validate(&module)
+        .map_err(|error| ShaderError::Validation {
+            name: name.to_owned(),
+            message: error.to_string(),
+        })?;
+
+    Ok(module)
+}
+
+fn validator() -> naga::valid::Validator {
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+}
+
+/// Runs `create` in an error scope and returns the validation error it caused, if any.
+/// Used to rebuild pipelines whose shaders might not fit together anymore.
+pub fn catch_validation_error<T>(
+    device: &wgpu::Device,
+    create: impl FnOnce(&wgpu::Device) -> T,
+) -> Result<T, ShaderError> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = create(device);
+
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(error) => Err(error.into()),
+        None => Ok(value),
+    }
+}
+
+/// A compiled permutation of the [`Shaders`] resource.
+#[derive(Debug)]
+pub struct Shader {
+    pub module: Arc<wgpu::ShaderModule>,
+    /// Incremented every time the module was recompiled, pipelines compare it to know when to rebuild.
+    pub generation: u64,
+    /// The files the module was preprocessed from.
+    files: Vec<String>,
+}
+
+/// Render world resource with the compiled shader permutations, keyed by their path
+/// in `assets/shaders` and their [`ShaderDefs`].
+///
+/// Shaders are preprocessed from the sources embedded with [`Shaders::embed`], unless
+/// the [`ShaderHotReloadPlugin`] set a directory to read them from.
+#[derive(Debug, Default)]
+pub struct Shaders {
+    embedded: HashMap<String, &'static str>,
+    permutations: HashMap<(String, ShaderDefs), Shader>,
+    directory: Option<PathBuf>,
+}
+
+impl Shaders {
+    /// Adds the source of a shader or an include compiled into the binary.
+    pub fn embed(&mut self, name: &str, source: &'static str) -> &mut Self {
+        self.embedded.insert(name.to_owned(), source);
+        self
+    }
+
+    /// Compiles the permutation of the shader `name` the first time it's requested.
+    ///
+    /// If the sources in the shader directory are broken, the error is logged and
+    /// the embedded sources are used instead.
+    pub fn load(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        defs: &ShaderDefs,
+    ) -> Result<&Shader, ShaderError> {
+        let key = (name.to_owned(), defs.clone());
+
+        if !self.permutations.contains_key(&key) {
+            let from_directory = match self.directory {
+                Some(_) => self.compile(device, name, defs, true).map(Some),
+                None => Ok(None),
+            };
+
+            let (module, files) = match from_directory {
+                Ok(Some(compiled)) => compiled,
+                Ok(None) => self.compile(device, name, defs, false)?,
+                Err(error) => {
+                    log::error!("{error}, using the embedded shader instead");
+                    self.compile(device, name, defs, false)?
+                }
+            };
+
+            self.permutations.insert(
+                key.clone(),
+                Shader {
+                    module: Arc::new(module),
+                    generation: 0,
+                    files,
+                },
+            );
+        }
+
+        Ok(&self.permutations[&key])
+    }
+
+    /// Whether a compiled permutation was preprocessed from `file`.
+    pub fn depends_on(&self, file: &str) -> bool {
+        self.permutations
+            .values()
+            .any(|shader| shader.files.iter().any(|name| name == file))
+    }
+
+    /// Recompiles every permutation preprocessed from `file` with the sources of the shader directory.
+    /// Permutations that fail to compile keep their last module.
+    pub fn reload(&mut self, device: &wgpu::Device, file: &str) -> Vec<ShaderError> {
+        let keys = self
+            .permutations
+            .iter()
+            .filter(|(_, shader)| shader.files.iter().any(|name| name == file))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        keys.into_iter()
+            .filter_map(|(name, defs)| {
+                let (module, files) = match self.compile(device, &name, &defs, true) {
+                    Ok(compiled) => compiled,
+                    Err(error) => return Some(error),
+                };
+
+                let shader = self.permutations.get_mut(&(name, defs))?;
+                shader.module = Arc::new(module);
+                shader.files = files;
+                shader.generation += 1;
+                None
+            })
+            .collect()
+    }
+
+    /// Preprocesses and compiles a permutation, returns the module and the files it was assembled from.
+    fn compile(
+        &self,
+        device: &wgpu::Device,
+        name: &str,
+        defs: &ShaderDefs,
+        from_directory: bool,
+    ) -> Result<(wgpu::ShaderModule, Vec<String>), ShaderError> {
+        let Preprocessed { source, files, .. } =
+            preprocess(name, defs, |file| match (from_directory, &self.directory) {
+                (true, Some(directory)) => std::fs::read_to_string(directory.join(file)),
+                _ => self
+                    .embedded
+                    .get(file)
+                    .map(|source| source.to_string())
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not embedded")),
+            })?;
+
+        let label = match defs.is_empty() {
+            true => name.to_owned(),
+            false => format!("{name} {defs}"),
+        };
+        validate_wgsl(&label, &source)?;
+
+        let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some(&label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        Ok((module, files))
+    }
+}
+
+/// Watches the shader directory and recompiles changed shaders, meant for debug builds.
+///
+/// Pipelines built from the [`Shaders`] resource are rebuilt on the next frame,
+/// shaders that fail to compile are logged and the last good module stays in use.
+#[derive(Debug, Clone)]
+pub struct ShaderHotReloadPlugin {
+    pub directory: PathBuf,
+    /// How often the modification times of the shaders are checked.
+    pub interval: Duration,
+}
+
+impl Default for ShaderHotReloadPlugin {
+    fn default() -> Self {
+        Self {
+            directory: Path::new(ASSETS_PATH).join("shaders"),
+            interval: Duration::from_millis(250),
+        }
+    }
+}
+
+impl Plugin for ShaderHotReloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            SystemDescriptor::new(ShaderHotReload {
+                directory: self.directory.clone(),
+                interval: self.interval,
+                changes: None,
+                stop: None,
+                watcher: None,
+            })
+            .in_stage(Stage::Render)
+            .label(ShaderHotReload::LABEL)
+            .before(RenderGraph::LABEL),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct ShaderHotReload {
+    directory: PathBuf,
+    interval: Duration,
+    changes: Option<Receiver<String>>,
+    /// Dropping it wakes the watcher thread up to stop.
+    stop: Option<Sender<()>>,
+    watcher: Option<JoinHandle<()>>,
+}
+
+impl ShaderHotReload {
+    pub const LABEL: SystemLabel = "shader_hot_reload";
+}
+
+impl System for ShaderHotReload {
+    fn init(&mut self, _context: &Context, world: &mut World) {
+        if world.get_resource::<Shaders>().is_none() {
+            world.insert_resource(Shaders::default());
+        }
+
+        let shaders = world.get_resource_mut::<Shaders>().unwrap();
+        // Another window already watches the directory
+        if shaders.directory.is_some() {
+            return;
+        }
+        shaders.directory = Some(self.directory.clone());
+
+        let (sender, changes) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let directory = self.directory.clone();
+        let interval = self.interval;
+        let spawned = thread::Builder::new()
+            .name("shader_watcher".to_owned())
+            .spawn(move || {
+                let mut modified = modification_times(&directory);
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    let current = modification_times(&directory);
+                    let changed = current
+                        .iter()
+                        .filter(|(name, time)| modified.get(*name) != Some(time))
+                        .map(|(name, _)| name.clone())
+                        .collect::<Vec<_>>();
+                    modified = current;
+
+                    if changed.into_iter().any(|name| sender.send(name).is_err()) {
+                        break;
+                    }
+                }
+            });
+
+        match spawned {
+            Ok(watcher) => {
+                log::info!("Watching `{}` for shader changes", self.directory.display());
+                self.changes = Some(changes);
+                self.stop = Some(stop);
+                self.watcher = Some(watcher);
+            }
+            Err(error) => log::error!("Failed to start the shader watcher: {error}"),
+        }
+    }
+
+    fn run(&mut self, context: &Context, world: &mut World) {
+        let changed = match self.changes {
+            Some(ref changes) => changes.try_iter().collect::<HashSet<_>>(),
+            None => return,
+        };
+        let shaders = match world.get_resource_mut::<Shaders>() {
+            Some(shaders) => shaders,
+            None => return,
+        };
+
+        for name in changed {
+            // Only shaders and includes that are in use are recompiled
+            if !shaders.depends_on(&name) {
+                continue;
+            }
+
+            let errors = shaders.reload(&context.device, &name);
+            match errors.is_empty() {
+                true => log::info!("Reloaded the shader `{name}`"),
+                false => errors.iter().for_each(|error| {
+                    log::error!("{error}\nKeeping the last good version of `{name}`")
+                }),
+            }
+        }
+    }
+}
+
+impl Drop for ShaderHotReload {
+    fn drop(&mut self) {
+        self.stop = None;
+        if let Some(watcher) = self.watcher.take() {
+            if watcher.join().is_err() {
+                log::error!("The shader watcher panicked");
+            }
+        }
+    }
+}
+
+/// Modification times of every file below `directory`, keyed by their path relative to it.
+fn modification_times(directory: &Path) -> HashMap<String, SystemTime> {
+    fn visit(root: &Path, directory: &Path, times: &mut HashMap<String, SystemTime>) {
+        let entries = match std::fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+
+            if metadata.is_dir() {
+                visit(root, &path, times);
+            } else if let (Ok(modified), Some(name)) =
+                (metadata.modified(), relative_name(root, &path))
+            {
+                times.insert(name, modified);
+            }
+        }
+    }
+
+    let mut times = HashMap::new();
+    visit(directory, directory, &mut times);
+    times
+}
+
+/// The path of a file relative to the shader directory, with forward slashes on every platform
+/// like the names passed to [`Shaders::load`].
+fn relative_name(root: &Path, path: &Path) -> Option<String> {
+    let name = path
+        .strip_prefix(root)
+        .ok()?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    Some(name)
+}
@@ -0,0 +1,403 @@
+//! A small C-like preprocessor for WGSL.
+//!
+//! ```wgsl
+//! #include "include/camera.wgsl"
+//! #define LIGHT_COUNT 4
+//!
+//! #ifdef HAS_NORMAL_MAP
+//! [[group(1), binding(2)]] var normal_map: texture_2d<f32>;
+//! #else
+//! // normals of the vertices
+//! #endif
+//! ```
+//!
+//! Includes are resolved relative to the shader directory and every file is included only once,
+//! so shared structs can be included by every file that needs them.
+//! `#ifndef`, `#undef` are supported as well, conditions can be nested.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, io,
+};
+
+use super::ShaderError;
+
+/// The defines of a shader permutation, compiled permutations are cached by them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShaderDefs(BTreeMap<String, String>);
+
+impl ShaderDefs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines a flag for `#ifdef`.
+    pub fn with(mut self, name: impl Into<String>) -> Self {
+        self.0.insert(name.into(), String::new());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromIterator<String> for ShaderDefs {
+    fn from_iter<I: IntoIterator<Item = String>>(flags: I) -> Self {
+        flags.into_iter().fold(Self::new(), ShaderDefs::with)
+    }
+}
+
+impl fmt::Display for ShaderDefs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let defs = self
+            .0
+            .iter()
+            .map(|(name, value)| match value.is_empty() {
+                true => name.clone(),
+                false => format!("{name}={value}"),
+            })
+            .collect::<Vec<_>>();
+
+        write!(f, "[{}]", defs.join(", "))
+    }
+}
+
+/// WGSL with the directives resolved.
+#[derive(Debug, Clone)]
+pub struct Preprocessed {
+    pub source: String,
+    /// The shader and every file it included, a change to any of them invalidates the source.
+    pub files: Vec<String>,
+    /// Names tested with `#ifdef` and `#ifndef`, the flags permutations can differ by.
+    pub flags: BTreeSet<String>,
+    /// Index into `files` and line number of every line of `source`.
+    lines: Vec<(usize, usize)>,
+}
+
+impl Preprocessed {
+    /// The file and line number a 1-based line of `source` came from.
+    pub fn origin(&self, line: usize) -> Option<(&str, usize)> {
+        let (file, line) = *self.lines.get(line.checked_sub(1)?)?;
+        Some((&self.files[file], line))
+    }
+}
+
+/// Resolves the directives of the shader `name`, `read` returns the source of a file in the shader directory.
+pub fn preprocess(
+    name: &str,
+    defs: &ShaderDefs,
+    read: impl FnMut(&str) -> io::Result<String>,
+) -> Result<Preprocessed, ShaderError> {
+    let mut preprocessor = Preprocessor {
+        read,
+        defines: defs.0.clone(),
+        files: vec![],
+        flags: BTreeSet::new(),
+        source: String::new(),
+        lines: vec![],
+    };
+    preprocessor.process(name)?;
+
+    Ok(Preprocessed {
+        source: preprocessor.source,
+        files: preprocessor.files,
+        flags: preprocessor.flags,
+        lines: preprocessor.lines,
+    })
+}
+
+#[derive(Debug)]
+struct Condition {
+    is_active: bool,
+    has_else: bool,
+}
+
+struct Preprocessor<R> {
+    read: R,
+    defines: BTreeMap<String, String>,
+    files: Vec<String>,
+    flags: BTreeSet<String>,
+    source: String,
+    lines: Vec<(usize, usize)>,
+}
+
+impl<R: FnMut(&str) -> io::Result<String>> Preprocessor<R> {
+    fn process(&mut self, file: &str) -> Result<(), ShaderError> {
+        if self.files.iter().any(|included| included == file) {
+            return Ok(());
+        }
+
+        let source = (self.read)(file).map_err(|source| ShaderError::Io {
+            path: file.into(),
+            source,
+        })?;
+        let file_index = self.files.len();
+        self.files.push(file.to_owned());
+
+        let mut conditions: Vec<Condition> = vec![];
+        let mut line_count = 0;
+
+        for (index, line) in source.lines().enumerate() {
+            line_count = index + 1;
+            let error = |message: String| ShaderError::Preprocess {
+                file: file.to_owned(),
+                line: index + 1,
+                message,
+            };
+            let is_active = conditions.iter().all(|condition| condition.is_active);
+
+            let directive = match line.trim_start().strip_prefix('#') {
+                Some(directive) => directive,
+                None => {
+                    if is_active {
+                        self.push_line(line);
+                    } else {
+                        // Keeps the line numbers of naga's errors in place
+                        self.source.push('\n');
+                    }
+                    self.lines.push((file_index, index + 1));
+                    continue;
+                }
+            };
+
+            let mut arguments = directive.split_whitespace();
+            let keyword = arguments.next().unwrap_or_default();
+            let mut argument = || {
+                arguments
+                    .next()
+                    .ok_or_else(|| error(format!("`#{keyword}` expects a name")))
+            };
+
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    let name = argument()?;
+                    self.flags.insert(name.to_owned());
+                    let is_defined = self.defines.contains_key(name);
+                    conditions.push(Condition {
+                        is_active: is_defined == (keyword == "ifdef"),
+                        has_else: false,
+                    });
+                }
+                "else" => match conditions.last_mut() {
+                    Some(condition) if !condition.has_else => {
+                        condition.is_active = !condition.is_active;
+                        condition.has_else = true;
+                    }
+                    Some(_) => return Err(error("second `#else` of an `#ifdef`".to_owned())),
+                    None => return Err(error("`#else` without an `#ifdef`".to_owned())),
+                },
+                "endif" => {
+                    if conditions.pop().is_none() {
+                        return Err(error("`#endif` without an `#ifdef`".to_owned()));
+                    }
+                }
+                "define" => {
+                    let name = argument()?.to_owned();
+                    let value = arguments.collect::<Vec<_>>().join(" ");
+                    if is_active {
+                        self.defines.insert(name, value);
+                    }
+                }
+                "undef" => {
+                    let name = argument()?;
+                    if is_active {
+                        self.defines.remove(name);
+                    }
+                }
+                "include" => {
+                    let path = directive.trim_start()["include".len()..].trim();
+                    let path = path
+                        .strip_prefix('"')
+                        .and_then(|path| path.strip_suffix('"'))
+                        .ok_or_else(|| error("`#include` expects a quoted path".to_owned()))?;
+                    if is_active {
+                        self.process(path)?;
+                    }
+                }
+                _ => return Err(error(format!("unknown directive `#{keyword}`"))),
+            }
+
+            if keyword != "include" || !is_active {
+                self.source.push('\n');
+                self.lines.push((file_index, index + 1));
+            }
+        }
+
+        match conditions.is_empty() {
+            true => Ok(()),
+            false => Err(ShaderError::Preprocess {
+                file: file.to_owned(),
+                line: line_count,
+                message: "missing `#endif`".to_owned(),
+            }),
+        }
+    }
+
+    /// Appends a line, replacing the identifiers defined with a value.
+    fn push_line(&mut self, line: &str) {
+        let defines = &self.defines;
+        let source = &mut self.source;
+
+        if defines.values().all(String::is_empty) {
+            source.push_str(line);
+            source.push('\n');
+            return;
+        }
+
+        let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let mut rest = line;
+        while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            // Skips the tail of numbers like `2u` or `1e10`
+            let (before, identifier) = rest.split_at(start);
+            let preceded_by_identifier = before.chars().last().is_some_and(is_identifier);
+            let end = identifier
+                .find(|c| !is_identifier(c))
+                .unwrap_or(identifier.len());
+            let (identifier, after) = identifier.split_at(end);
+
+            source.push_str(before);
+            match defines.get(identifier) {
+                Some(value) if !value.is_empty() && !preceded_by_identifier => {
+                    source.push_str(value)
+                }
+                _ => source.push_str(identifier),
+            }
+            rest = after;
+        }
+        source.push_str(rest);
+        source.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Preprocesses `source` as `main.wgsl`, without any other files.
+    fn preprocess_source(source: &str, defs: &ShaderDefs) -> Result<Preprocessed, ShaderError> {
+        preprocess("main.wgsl", defs, |file| match file {
+            "main.wgsl" => Ok(source.to_owned()),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, file.to_owned())),
+        })
+    }
+
+    /// The non-empty lines of the source, trimmed.
+    fn lines(preprocessed: &Preprocessed) -> Vec<&str> {
+        preprocessed
+            .source
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn includes_are_resolved_once() {
+        let preprocessed = preprocess("main.wgsl", &ShaderDefs::new(), |file| match file {
+            "main.wgsl" => Ok("#include \"a.wgsl\"\n#include \"b.wgsl\"\nmain".to_owned()),
+            "a.wgsl" => Ok("#include \"b.wgsl\"\na".to_owned()),
+            "b.wgsl" => Ok("b".to_owned()),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, file.to_owned())),
+        })
+        .unwrap();
+
+        assert_eq!(lines(&preprocessed), ["b", "a", "main"]);
+        assert_eq!(preprocessed.files, ["main.wgsl", "a.wgsl", "b.wgsl"]);
+    }
+
+    #[test]
+    fn nested_conditions_select_branches() {
+        let source = "\
+#ifdef A
+#ifndef B
+a_without_b
+#else
+a_with_b
+#endif
+#else
+no_a
+#endif";
+        let branch = |defs: ShaderDefs| {
+            let preprocessed = preprocess_source(source, &defs).unwrap();
+            lines(&preprocessed).concat()
+        };
+
+        assert_eq!(branch(ShaderDefs::new()), "no_a");
+        assert_eq!(branch(ShaderDefs::new().with("A")), "a_without_b");
+        assert_eq!(branch(ShaderDefs::new().with("A").with("B")), "a_with_b");
+        assert_eq!(branch(ShaderDefs::new().with("B")), "no_a");
+
+        let preprocessed = preprocess_source(source, &ShaderDefs::new()).unwrap();
+        assert_eq!(
+            preprocessed.flags.into_iter().collect::<Vec<_>>(),
+            ["A", "B"]
+        );
+    }
+
+    #[test]
+    fn defines_replace_whole_identifiers() {
+        let source = "\
+#define COUNT 4u
+#define FLAG
+let a = array<f32, COUNT>();
+let COUNT_2 = COUNT + 2u;
+#undef COUNT
+let b = COUNT;";
+        let preprocessed = preprocess_source(source, &ShaderDefs::new()).unwrap();
+
+        assert_eq!(
+            lines(&preprocessed),
+            [
+                "let a = array<f32, 4u>();",
+                "let COUNT_2 = 4u + 2u;",
+                "let b = COUNT;"
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_map_to_their_origin() {
+        let preprocessed = preprocess("main.wgsl", &ShaderDefs::new(), |file| match file {
+            "main.wgsl" => Ok("#ifdef A\nskipped\n#endif\n#include \"a.wgsl\"\nmain".to_owned()),
+            _ => Ok("first\nsecond".to_owned()),
+        })
+        .unwrap();
+
+        let line_of = |text: &str| {
+            preprocessed
+                .source
+                .lines()
+                .position(|line| line == text)
+                .unwrap()
+                + 1
+        };
+
+        assert_eq!(preprocessed.origin(line_of("second")), Some(("a.wgsl", 2)));
+        assert_eq!(preprocessed.origin(line_of("main")), Some(("main.wgsl", 5)));
+        assert_eq!(preprocessed.origin(0), None);
+    }
+
+    #[test]
+    fn errors_report_their_file_and_line() {
+        let error_at = |source: &str| match preprocess_source(source, &ShaderDefs::new()) {
+            Err(ShaderError::Preprocess { file, line, .. }) => (file, line),
+            result => panic!("expected a preprocessor error, got {result:?}"),
+        };
+
+        assert_eq!(error_at("a\n#ifdef A\nb"), ("main.wgsl".to_owned(), 3));
+        assert_eq!(error_at("a\nb\n#endif"), ("main.wgsl".to_owned(), 3));
+        assert_eq!(
+            error_at("#ifdef A\n#else\n#else\n#endif"),
+            ("main.wgsl".to_owned(), 3)
+        );
+        assert_eq!(error_at("#version 450"), ("main.wgsl".to_owned(), 1));
+    }
+
+    #[test]
+    fn missing_includes_are_io_errors() {
+        let result = preprocess_source("#include \"missing.wgsl\"", &ShaderDefs::new());
+
+        assert!(matches!(result, Err(ShaderError::Io { .. })));
+    }
+}
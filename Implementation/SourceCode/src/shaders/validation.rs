@@ -0,0 +1,287 @@
+//! Validates every shader of a directory with naga, without creating a device.
+//!
+//! WGSL shaders are preprocessed and validated in every permutation of the flags they test,
+//! SPIR-V modules are parsed as they are, the shaders of `SKIPPED` are left out.
+//! Run with `cargo run -- --validate-shaders`.
+
+use std::{error::Error, fmt, iter, path::Path};
+
+use naga::front::{spv, wgsl};
+
+use super::{preprocess, relative_name, validator, Preprocessed, ShaderDefs, ShaderError};
+
+/// Files in this directory are only validated through the shaders including them.
+const INCLUDE_DIRECTORY: &str = "include/";
+
+/// Shaders naga 0.8 can't parse, with the reason they are kept.
+const SKIPPED: &[(&str, &str)] = &[
+    (
+        "cube/shader.wgsl",
+        "newer WGSL syntax, `cube/shader.old.wgsl` is validated instead",
+    ),
+    (
+        "triangle/shader.frag.modern.wgsl",
+        "newer WGSL syntax, `triangle/shader.frag.wgsl` is validated instead",
+    ),
+    (
+        "triangle/shader.vert.modern.wgsl",
+        "newer WGSL syntax, `triangle/shader.vert.wgsl` is validated instead",
+    ),
+    ("model/model.frag.spv", "specialization constants"),
+    ("model/model.frag.vk.wgsl", "specialization constants"),
+];
+
+/// Shaders testing more flags are only validated with none and each single flag.
+const MAX_PERMUTATION_FLAGS: usize = 8;
+
+/// An error of a shader, formatted like compiler errors: `file:line:column: error: message`.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// Path relative to the shader directory, an include if the error is located in one.
+    pub file: String,
+    /// The shader that was validated, differs from `file` if the error is located in an include.
+    pub shader: String,
+    /// 1-based line and column, `None` for SPIR-V and errors without a span.
+    pub location: Option<(usize, usize)>,
+    pub permutation: ShaderDefs,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(f, "{}:{line}:{column}: error: ", self.file)?,
+            None => write!(f, "{}: error: ", self.file)?,
+        }
+        write!(f, "{}", self.message)?;
+
+        if self.shader != self.file {
+            write!(f, "\n    included from {}", self.shader)?;
+        }
+
+        match self.permutation.is_empty() {
+            true => Ok(()),
+            false => write!(f, "\n    in permutation {}", self.permutation),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Number of modules that were validated, counting every permutation.
+    pub modules: usize,
+    /// Shaders of `SKIPPED` that were found.
+    pub skipped: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+/// Validates the `.wgsl` and `.spv` files below `directory`.
+pub fn validate_directory(directory: &Path) -> Result<Report, ShaderError> {
+    let mut files = vec![];
+    collect_files(directory, directory, &mut files)?;
+    files.sort();
+
+    let mut report = Report::default();
+    for name in files {
+        if SKIPPED.iter().any(|(skipped, _)| *skipped == name) {
+            report.skipped += 1;
+        } else if name.ends_with(".wgsl") && !name.starts_with(INCLUDE_DIRECTORY) {
+            validate_wgsl_file(directory, &name, &mut report);
+        } else if name.ends_with(".spv") {
+            validate_spv_file(directory, &name, &mut report);
+        }
+    }
+
+    Ok(report)
+}
+
+fn validate_wgsl_file(directory: &Path, name: &str, report: &mut Report) {
+    let read = |file: &str| std::fs::read_to_string(directory.join(file));
+
+    let unflagged = match preprocess(name, &ShaderDefs::new(), read) {
+        Ok(preprocessed) => preprocessed,
+        Err(error) => return report.diagnostics.push(preprocess_error(name, error)),
+    };
+
+    let flags = unflagged.flags.iter().cloned().collect::<Vec<_>>();
+    let permutations: Vec<ShaderDefs> = match flags.len() <= MAX_PERMUTATION_FLAGS {
+        true => (1..1_usize << flags.len())
+            .map(|mask| {
+                flags
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| mask & (1 << index) != 0)
+                    .map(|(_, flag)| flag.clone())
+                    .collect()
+            })
+            .collect(),
+        false => flags
+            .iter()
+            .map(|flag| ShaderDefs::new().with(flag.clone()))
+            .collect(),
+    };
+
+    report.modules += 1;
+    report
+        .diagnostics
+        .extend(validate_preprocessed(&unflagged, &ShaderDefs::new()));
+
+    for defs in permutations {
+        report.modules += 1;
+        let diagnostic = match preprocess(name, &defs, read) {
+            Ok(preprocessed) => validate_preprocessed(&preprocessed, &defs),
+            Err(error) => Some(Diagnostic {
+                permutation: defs,
+                ..preprocess_error(name, error)
+            }),
+        };
+        report.diagnostics.extend(diagnostic);
+    }
+}
+
+fn validate_preprocessed(preprocessed: &Preprocessed, defs: &ShaderDefs) -> Option<Diagnostic> {
+    let source = &preprocessed.source;
+    let located = |line: usize, column: usize, message: String| {
+        let (file, line) = preprocessed
+            .origin(line)
+            .unwrap_or((&preprocessed.files[0], line));
+
+        Diagnostic {
+            file: file.to_owned(),
+            shader: preprocessed.files[0].clone(),
+            location: Some((line, column)),
+            permutation: defs.clone(),
+            message,
+        }
+    };
+
+    let module = match wgsl::parse_str(source) {
+        Ok(module) => module,
+        Err(error) => {
+            let (line, column) = error.location(source);
+            return Some(located(line, column, error.to_string()));
+        }
+    };
+
+    let error = validator().validate(&module).err()?;
+    let spans = error
+        .spans()
+        .filter_map(|(span, description)| {
+            let (line, column) = line_column(source, span.to_range()?.start);
+            let (file, line) = preprocessed.origin(line)?;
+            Some(format!("\n    {file}:{line}:{column}: {description}"))
+        })
+        .collect::<Vec<_>>();
+
+    let message = error_chain(&error) + &spans.concat();
+    // The last span is the innermost, usually the expression at fault
+    let innermost_span = error
+        .spans()
+        .filter_map(|(span, _)| span.to_range())
+        .last()
+        .map(|range| line_column(source, range.start));
+
+    Some(match innermost_span {
+        Some((line, column)) => located(line, column, message),
+        None => Diagnostic {
+            file: preprocessed.files[0].clone(),
+            shader: preprocessed.files[0].clone(),
+            location: None,
+            permutation: defs.clone(),
+            message,
+        },
+    })
+}
+
+fn validate_spv_file(directory: &Path, name: &str, report: &mut Report) {
+    report.modules += 1;
+
+    let diagnostic = |message: String| Diagnostic {
+        file: name.to_owned(),
+        shader: name.to_owned(),
+        location: None,
+        permutation: ShaderDefs::new(),
+        message,
+    };
+
+    let bytes = match std::fs::read(directory.join(name)) {
+        Ok(bytes) => bytes,
+        Err(error) => return report.diagnostics.push(diagnostic(error.to_string())),
+    };
+
+    let module = match spv::parse_u8_slice(&bytes, &spv::Options::default()) {
+        Ok(module) => module,
+        Err(error) => return report.diagnostics.push(diagnostic(error_chain(&error))),
+    };
+
+    if let Err(error) = validator().validate(&module) {
+        report.diagnostics.push(diagnostic(error_chain(&error)));
+    }
+}
+
+fn preprocess_error(name: &str, error: ShaderError) -> Diagnostic {
+    let (file, location, message) = match error {
+        ShaderError::Preprocess {
+            file,
+            line,
+            message,
+        } => (file, Some((line, 1)), message),
+        error => (name.to_owned(), None, error.to_string()),
+    };
+
+    Diagnostic {
+        file,
+        shader: name.to_owned(),
+        location,
+        permutation: ShaderDefs::new(),
+        message,
+    }
+}
+
+/// The message of an error followed by the messages of its sources.
+fn error_chain(error: &(dyn Error + 'static)) -> String {
+    iter::successors(Some(error), |error| (*error).source())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+/// 1-based line and column of a byte offset.
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+fn collect_files(
+    root: &Path,
+    directory: &Path,
+    files: &mut Vec<String>,
+) -> Result<(), ShaderError> {
+    let io_error = |source| ShaderError::Io {
+        path: directory.to_owned(),
+        source,
+    };
+
+    for entry in std::fs::read_dir(directory).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Some(name) = relative_name(root, &path) {
+            files.push(name);
+        }
+    }
+
+    Ok(())
+}
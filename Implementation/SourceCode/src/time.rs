@@ -0,0 +1,256 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Frame timing, updated by the schedule at the start of every frame.
+#[derive(Debug, Clone)]
+pub struct Time {
+    startup: Instant,
+    last_update: Option<Instant>,
+    delta: Duration,
+    elapsed: Duration,
+    frame_count: u64,
+    fps: f64,
+    fixed_timestep: Duration,
+    accumulator: Duration,
+}
+
+impl Time {
+    /// 60 updates per second.
+    pub const DEFAULT_FIXED_TIMESTEP: Duration = Duration::from_nanos(16_666_667);
+    /// Upper bound for the time accumulated in a single frame, so a long stall
+    /// (e.g. loading a model) doesn't trigger hundreds of fixed updates.
+    pub const MAX_ACCUMULATED: Duration = Duration::from_millis(250);
+    /// Shorter timesteps are clamped, a zero timestep would never be consumed from the accumulator.
+    pub const MIN_FIXED_TIMESTEP: Duration = Duration::from_millis(1);
+    /// Weight of the newest frame in the smoothed FPS.
+    const FPS_SMOOTHING: f64 = 0.1;
+
+    /// `fixed_timestep` is clamped to [`Time::MIN_FIXED_TIMESTEP`].
+    pub fn new(fixed_timestep: Duration) -> Self {
+        let mut time = Self {
+            startup: Instant::now(),
+            last_update: None,
+            delta: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            frame_count: 0,
+            fps: 0.0,
+            fixed_timestep: Time::DEFAULT_FIXED_TIMESTEP,
+            accumulator: Duration::ZERO,
+        };
+        time.set_fixed_timestep(fixed_timestep);
+        time
+    }
+
+    pub fn update(&mut self) {
+        self.update_with_instant(Instant::now());
+    }
+
+    pub fn update_with_instant(&mut self, now: Instant) {
+        self.delta = match self.last_update {
+            Some(last_update) => now - last_update,
+            None => Duration::ZERO,
+        };
+        self.last_update = Some(now);
+        self.elapsed = now - self.startup;
+        self.frame_count += 1;
+
+        let delta_seconds = self.delta.as_secs_f64();
+        if delta_seconds > 0.0 {
+            let fps = 1.0 / delta_seconds;
+            self.fps = match self.fps == 0.0 {
+                true => fps,
+                false => self.fps + (fps - self.fps) * Time::FPS_SMOOTHING,
+            };
+        }
+
+        self.accumulator = (self.accumulator + self.delta).min(Time::MAX_ACCUMULATED);
+    }
+
+    /// Consumes one fixed timestep from the accumulator, returns `false` once less than a step is left.
+    pub fn expend_fixed_step(&mut self) -> bool {
+        match self.accumulator >= self.fixed_timestep {
+            true => {
+                self.accumulator -= self.fixed_timestep;
+                true
+            }
+            false => false,
+        }
+    }
+
+    #[inline]
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    #[inline]
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    #[inline]
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+
+    #[inline]
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Exponentially smoothed frames per second.
+    #[inline]
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    #[inline]
+    pub fn fixed_timestep(&self) -> Duration {
+        self.fixed_timestep
+    }
+
+    #[inline]
+    pub fn fixed_delta_seconds(&self) -> f32 {
+        self.fixed_timestep.as_secs_f32()
+    }
+
+    /// Clamped to [`Time::MIN_FIXED_TIMESTEP`], debug builds assert that it isn't zero.
+    pub fn set_fixed_timestep(&mut self, fixed_timestep: Duration) {
+        debug_assert!(
+            !fixed_timestep.is_zero(),
+            "the fixed timestep must not be zero"
+        );
+        self.fixed_timestep = fixed_timestep.max(Time::MIN_FIXED_TIMESTEP);
+    }
+
+    /// How far the accumulator is into the next fixed step, in `0.0..1.0`.
+    /// Used to interpolate between the last two fixed updates when rendering.
+    pub fn fixed_overstep(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.fixed_timestep.as_secs_f32()
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Time::new(Time::DEFAULT_FIXED_TIMESTEP)
+    }
+}
+
+/// Software frame rate cap, stored as a resource so it can be changed at runtime.
+/// The render thread caps its frames at the target of the resource, the event loop never waits for it.
+///
+/// Sleeps for most of the remaining frame time and spins for the rest,
+/// since the OS scheduler often oversleeps by a millisecond or more.
+#[derive(Debug, Clone, Default)]
+pub struct FrameLimiter {
+    target_frame_time: Option<Duration>,
+    last_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+    /// Remaining time that is spun instead of slept.
+    const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+    /// `None` leaves the frame rate uncapped.
+    pub fn new(target_fps: Option<f64>) -> Self {
+        let mut limiter = FrameLimiter::default();
+        limiter.set_target_fps(target_fps);
+        limiter
+    }
+
+    pub fn target_fps(&self) -> Option<f64> {
+        self.target_frame_time
+            .map(|frame_time| 1.0 / frame_time.as_secs_f64())
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: Option<f64>) {
+        self.target_frame_time = target_fps
+            .filter(|fps| *fps > 0.0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps));
+    }
+
+    /// Blocks until the target frame time has passed since the last call.
+    pub fn wait(&mut self) {
+        if let (Some(target_frame_time), Some(last_frame)) =
+            (self.target_frame_time, self.last_frame)
+        {
+            let deadline = last_frame + target_frame_time;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining > FrameLimiter::SPIN_THRESHOLD {
+                thread::sleep(remaining - FrameLimiter::SPIN_THRESHOLD);
+            }
+            while Instant::now() < deadline {
+                std::hint::spin_loop();
+            }
+        }
+
+        self.last_frame = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A time that was updated once at `start`, so the next update measures a delta.
+    fn started(fixed_timestep: Duration) -> (Time, Instant) {
+        let start = Instant::now();
+        let mut time = Time::new(fixed_timestep);
+        time.update_with_instant(start);
+        (time, start)
+    }
+
+    fn fixed_steps(time: &mut Time) -> usize {
+        std::iter::from_fn(|| time.expend_fixed_step().then_some(())).count()
+    }
+
+    #[test]
+    fn first_update_has_no_delta() {
+        let (mut time, _) = started(Duration::from_millis(10));
+
+        assert_eq!(time.delta(), Duration::ZERO);
+        assert_eq!(time.frame_count(), 1);
+        assert_eq!(fixed_steps(&mut time), 0);
+    }
+
+    #[test]
+    fn fixed_steps_follow_the_delta() {
+        let (mut time, start) = started(Duration::from_millis(10));
+
+        time.update_with_instant(start + Duration::from_millis(35));
+        assert_eq!(time.delta(), Duration::from_millis(35));
+        assert_eq!(fixed_steps(&mut time), 3);
+        assert!((time.fixed_overstep() - 0.5).abs() < 1e-4);
+
+        // The remaining 5ms carry over into the next frame
+        time.update_with_instant(start + Duration::from_millis(40));
+        assert_eq!(fixed_steps(&mut time), 1);
+        assert_eq!(time.fixed_overstep(), 0.0);
+    }
+
+    #[test]
+    fn accumulator_is_clamped() {
+        let (mut time, start) = started(Duration::from_millis(10));
+
+        time.update_with_instant(start + Duration::from_secs(5));
+        assert_eq!(
+            fixed_steps(&mut time),
+            (Time::MAX_ACCUMULATED.as_millis() / 10) as usize
+        );
+    }
+
+    #[test]
+    fn short_timesteps_are_clamped() {
+        let mut time = Time::default();
+        time.set_fixed_timestep(Duration::from_micros(10));
+
+        assert_eq!(time.fixed_timestep(), Time::MIN_FIXED_TIMESTEP);
+    }
+}
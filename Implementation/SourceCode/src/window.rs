@@ -0,0 +1,99 @@
+use winit::{dpi::PhysicalSize, window::WindowId};
+
+use crate::schedule::SystemDescriptor;
+
+#[derive(Debug, Clone)]
+pub struct WindowDescriptor {
+    pub title: String,
+    pub size: PhysicalSize<u32>,
+}
+
+impl WindowDescriptor {
+    pub fn new(title: impl Into<String>, size: PhysicalSize<u32>) -> Self {
+        Self {
+            title: title.into(),
+            size,
+        }
+    }
+}
+
+/// A window to open together with the systems that draw into it.
+///
+/// The systems only receive the events of their window and run with its [`Context`](crate::app::Context).
+/// They follow the frames of the primary window: each of its redraws runs their `Update`, `PostUpdate`
+/// and `Extract` stages after the primary ones, and renders every window. `PreUpdate` and `FixedUpdate`
+/// only run for the primary window, systems in these stages of a window request never run.
+///
+/// ```ignore
+/// WindowRequest::new(WindowDescriptor::new("Inspector", PhysicalSize::new(400, 600)))
+///     .with_system(SystemDescriptor::new(InspectorRenderer::default()).in_stage(Stage::Render));
+/// ```
+#[derive(Debug)]
+pub struct WindowRequest {
+    pub descriptor: WindowDescriptor,
+    pub systems: Vec<SystemDescriptor>,
+}
+
+impl WindowRequest {
+    pub fn new(descriptor: WindowDescriptor) -> Self {
+        Self {
+            descriptor,
+            systems: vec![],
+        }
+    }
+
+    pub fn with_system(mut self, system: impl Into<SystemDescriptor>) -> Self {
+        self.systems.push(system.into());
+        self
+    }
+}
+
+/// Resource listing the open windows, systems request new ones through it at runtime.
+#[derive(Debug, Default)]
+pub struct Windows {
+    primary: Option<WindowId>,
+    secondary: Vec<WindowId>,
+    requests: Vec<WindowRequest>,
+}
+
+impl Windows {
+    /// The window is created once the current event loop iteration has been processed.
+    pub fn create(&mut self, request: WindowRequest) {
+        self.requests.push(request);
+    }
+
+    /// The window the app was created with, `None` for headless apps.
+    #[inline]
+    pub fn primary(&self) -> Option<WindowId> {
+        self.primary
+    }
+
+    /// Every open window, the primary one first.
+    pub fn iter(&self) -> impl Iterator<Item = WindowId> + '_ {
+        self.primary.iter().chain(self.secondary.iter()).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.primary.iter().len() + self.secondary.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn set_primary(&mut self, id: WindowId) {
+        self.primary = Some(id);
+    }
+
+    pub(crate) fn add(&mut self, id: WindowId) {
+        self.secondary.push(id);
+    }
+
+    pub(crate) fn remove(&mut self, id: WindowId) {
+        self.secondary.retain(|window| *window != id);
+    }
+
+    pub(crate) fn take_requests(&mut self) -> Vec<WindowRequest> {
+        std::mem::take(&mut self.requests)
+    }
+}